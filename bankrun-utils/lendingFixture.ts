@@ -0,0 +1,260 @@
+import assert from 'node:assert';
+import { createHash } from 'crypto';
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, TransactionInstruction } from '@solana/web3.js';
+import { AccountLayout, getAssociatedTokenAddressSync, TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { BN, Program } from '@coral-xyz/anchor';
+import { ProgramTestContext, startAnchor } from 'solana-bankrun';
+import { createAccount, createMint, mintTo } from 'spl-token-bankrun';
+
+import IDL from '../target/idl/lending.json';
+import { Lending } from '../target/types/lending';
+import { BankrunContextWrapper } from './bankrunConnection';
+
+export const PYTH_RECEIVER_PROGRAM_ID = new PublicKey('rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ');
+const PRICE_UPDATE_DISCRIMINATOR = createHash('sha256').update('account:PriceUpdateV2').digest().subarray(0, 8);
+const PRICE_EXPONENT = -8;
+
+export const USDC_DECIMALS = 6;
+export const SOL_DECIMALS = 9;
+export const USDC_FEED_ID = Array(32).fill(1);
+export const SOL_FEED_ID = Array(32).fill(2);
+
+/** Codes of the lending program's `ErrorCode` variants the tests expect. */
+export const LendingError = {
+  OverBorrowableAmount: 6001,
+  Unauthorized: 6002,
+  Unhealthy: 6009,
+  NotUndercollateralized: 6010,
+  InvalidMint: 6011,
+  NoBadDebt: 6016,
+  BelowMinimumBorrow: 6018,
+  DebtBelowMinimum: 6019,
+  SlippageExceeded: 6025,
+};
+
+export const SETTLE_ALL = new BN('18446744073709551615');
+
+type BankConfigParams = Parameters<Program<Lending>['methods']['updateBank']>[0];
+
+/** `update_bank` params changing nothing, to spread the settings a test cares about over. */
+const UNCHANGED_BANK_CONFIG: BankConfigParams = {
+  paused: null,
+  depositCap: null,
+  borrowCap: null,
+  liquidationThreshold: null,
+  maxLtv: null,
+  liquidationBonus: null,
+  liquidationMaxBonus: null,
+  liquidationBonusRamp: null,
+  liquidationProtocolFee: null,
+  closeFactor: null,
+  interestRate: null,
+  minBorrow: null,
+  minDebt: null,
+  borrowFee: null,
+  referrerFeeShare: null,
+  fixedRatePremium: null,
+  maxFixedTerm: null,
+  fixedGracePeriod: null,
+  oracleFeedId: null,
+};
+
+export const usdc = (amount: number) => new BN(Math.round(amount * 10 ** USDC_DECIMALS));
+export const sol = (amount: number) => new BN(Math.round(amount * 10 ** SOL_DECIMALS));
+
+/** Rejects unless `promise` fails with custom error `code`, whether or not Anchor could parse the logs. */
+export async function expectError(promise: Promise<unknown>, code: number) {
+  await assert.rejects(promise, (err: any) => {
+    const text = [String(err), ...(err.logs ?? [])].join('\n');
+    return text.includes(`Error Number: ${code}.`) || text.includes(`custom program error: 0x${code.toString(16)}`);
+  });
+}
+
+/** A `PriceUpdateV2` account body as the Pyth receiver writes it, fully verified. */
+export function priceUpdateData(feedId: number[], price: BN, exponent: number, publishTime: BN): Buffer {
+  const i64 = (value: BN) => value.toTwos(64).toArrayLike(Buffer, 'le', 8);
+  const exponentBytes = Buffer.alloc(4);
+  exponentBytes.writeInt32LE(exponent);
+
+  return Buffer.concat([
+    PRICE_UPDATE_DISCRIMINATOR,
+    Buffer.alloc(32), // write_authority
+    Buffer.from([1]), // VerificationLevel::Full
+    Buffer.from(feedId),
+    i64(price),
+    i64(new BN(0)), // conf
+    exponentBytes,
+    i64(publishTime),
+    i64(publishTime), // prev_publish_time
+    i64(price), // ema_price
+    i64(new BN(0)), // ema_conf
+    i64(new BN(0)), // posted_slot
+  ]);
+}
+
+/**
+ * A market with a USDC and a SOL bank priced by oracle accounts the test controls, and a
+ * lender obligation (index 1) supplying both banks. Obligation 0 is left for the test.
+ */
+export class LendingFixture {
+  readonly usdcPriceUpdate = Keypair.generate().publicKey;
+  readonly solPriceUpdate = Keypair.generate().publicKey;
+
+  private constructor(
+    readonly context: ProgramTestContext,
+    readonly wrapper: BankrunContextWrapper,
+    readonly program: Program<Lending>,
+    readonly signer: Keypair,
+    readonly mintUSDC: PublicKey,
+    readonly mintSOL: PublicKey,
+  ) {}
+
+  static async create(): Promise<LendingFixture> {
+    // Deploys every program in Anchor.toml, mock_amm included.
+    const context = await startAnchor('', [], []);
+    const wrapper = new BankrunContextWrapper(context);
+    const program = new Program<Lending>(IDL as Lending, wrapper.provider);
+    const signer = context.payer;
+
+    // @ts-ignore
+    const mintUSDC = await createMint(context.banksClient, signer, signer.publicKey, null, USDC_DECIMALS);
+    // @ts-ignore
+    const mintSOL = await createMint(context.banksClient, signer, signer.publicKey, null, SOL_DECIMALS);
+    const fixture = new LendingFixture(context, wrapper, program, signer, mintUSDC, mintSOL);
+
+    for (const [mint, feedId] of [[mintUSDC, USDC_FEED_ID], [mintSOL, SOL_FEED_ID]] as const) {
+      await program.methods
+        .initializeBank(new BN(8000), new BN(7500), [...feedId])
+        .accounts({ signer: signer.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID, programData: null })
+        .rpc();
+    }
+
+    await fixture.fundTokens(signer, usdc(10_000_000), sol(100_000));
+    await fixture.setUsdcPrice(1);
+    await fixture.setSolPrice(100);
+
+    const lender = await fixture.initUser(1);
+    await fixture.deposit(lender, mintUSDC, usdc(1_000_000));
+    await fixture.deposit(lender, mintSOL, sol(10_000));
+    return fixture;
+  }
+
+  async setUsdcPrice(usd: number) {
+    await this.setPrice(this.usdcPriceUpdate, USDC_FEED_ID, usd);
+  }
+
+  async setSolPrice(usd: number) {
+    await this.setPrice(this.solPriceUpdate, SOL_FEED_ID, usd);
+  }
+
+  /** Writes a price published at the current bank time, so it stays fresh until the clock moves. */
+  async setPrice(address: PublicKey, feedId: number[], usd: number) {
+    const clock = await this.context.banksClient.getClock();
+    const price = new BN(Math.round(usd * 10 ** -PRICE_EXPONENT));
+    this.context.setAccount(address, {
+      lamports: LAMPORTS_PER_SOL,
+      data: priceUpdateData(feedId, price, PRICE_EXPONENT, new BN(clock.unixTimestamp.toString())),
+      owner: PYTH_RECEIVER_PROGRAM_ID,
+      executable: false,
+    });
+  }
+
+  priceUpdate(mint: PublicKey): PublicKey {
+    return mint.equals(this.mintUSDC) ? this.usdcPriceUpdate : this.solPriceUpdate;
+  }
+
+  otherMint(mint: PublicKey): PublicKey {
+    return mint.equals(this.mintUSDC) ? this.mintSOL : this.mintUSDC;
+  }
+
+  userAccount(index: number, owner = this.signer.publicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), owner.toBuffer(), Buffer.from([index])],
+      this.program.programId
+    )[0];
+  }
+
+  bank(mint: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync([Buffer.from('bank'), mint.toBuffer()], this.program.programId)[0];
+  }
+
+  ata(mint: PublicKey, owner = this.signer.publicKey): PublicKey {
+    return getAssociatedTokenAddressSync(mint, owner);
+  }
+
+  async tokenBalance(address: PublicKey): Promise<BN> {
+    const account = await this.context.banksClient.getAccount(address);
+    return new BN(AccountLayout.decode(Buffer.from(account.data)).amount.toString());
+  }
+
+  /** A new wallet with lamports and both tokens, e.g. a liquidator. */
+  async fundedKeypair(usdcAmount: BN, solAmount: BN): Promise<Keypair> {
+    const keypair = Keypair.generate();
+    await this.wrapper.fundKeypair(keypair, 10 * LAMPORTS_PER_SOL);
+    await this.fundTokens(keypair, usdcAmount, solAmount);
+    return keypair;
+  }
+
+  async fundTokens(owner: Keypair, usdcAmount: BN, solAmount: BN) {
+    for (const [mint, amount] of [[this.mintUSDC, usdcAmount], [this.mintSOL, solAmount]] as const) {
+      // @ts-ignore
+      const account = await createAccount(this.context.banksClient, this.signer, mint, owner.publicKey);
+      // @ts-ignore
+      await mintTo(this.context.banksClient, this.signer, mint, account, this.signer, BigInt(amount.toString()));
+    }
+  }
+
+  async updateBank(mint: PublicKey, params: Partial<BankConfigParams>) {
+    await this.program.methods
+      .updateBank({ ...UNCHANGED_BANK_CONFIG, ...params })
+      .accounts({ authority: this.signer.publicKey, mint })
+      .rpc();
+  }
+
+  async initUser(index: number): Promise<PublicKey> {
+    await this.program.methods
+      .initializeUser(index, this.mintUSDC)
+      .accounts({ signer: this.signer.publicKey })
+      .rpc();
+    return this.userAccount(index);
+  }
+
+  async deposit(userAccount: PublicKey, mint: PublicKey, amount: BN) {
+    await this.program.methods
+      .deposit(amount)
+      .accounts({ signer: this.signer.publicKey, mint, userAccount, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+  }
+
+  async borrow(userAccount: PublicKey, mint: PublicKey, amount: BN) {
+    const collateralMint = this.otherMint(mint);
+    await this.program.methods
+      .borrow(amount)
+      .accounts({
+        signer: this.signer.publicKey,
+        mint,
+        collateralMint,
+        userAccount,
+        owner: this.signer.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        priceUpdate: this.priceUpdate(collateralMint),
+        borrowedPriceUpdate: this.priceUpdate(mint),
+        referrerTokenAccount: null,
+      })
+      .preInstructions([await this.refreshObligation(userAccount)])
+      .rpc();
+  }
+
+  refreshObligation(userAccount: PublicKey): Promise<TransactionInstruction> {
+    return this.program.methods
+      .refreshObligation()
+      .accounts({
+        mint: this.mintSOL,
+        priceUpdate: this.solPriceUpdate,
+        otherMint: this.mintUSDC,
+        otherPriceUpdate: this.usdcPriceUpdate,
+        userAccount,
+      })
+      .instruction();
+  }
+}
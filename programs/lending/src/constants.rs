@@ -3,4 +3,41 @@ use anchor_lang::prelude::*;
 #[constant]
 pub const SOL_USD_FEED_ID: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
 pub const USDC_USD_FEED_ID: &str = "0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
-pub const MAXIMUM_AGE: u64 = 100;
\ No newline at end of file
+pub const MAXIMUM_AGE: u64 = 100;
+/// Denominator for every basis-point parameter.
+pub const BPS: u64 = 10_000;
+/// USD values are computed with this many decimals.
+pub const VALUE_DECIMALS: u32 = 6;
+/// Debt worth less than this (in `VALUE_DECIMALS` USD) can be liquidated in full, ignoring the close factor.
+pub const LIQUIDATION_DUST_VALUE: u128 = 10_000_000;
+/// Partial liquidations are sized to bring an obligation back to this health, in bps.
+pub const LIQUIDATION_TARGET_HEALTH: u64 = 11_000;
+pub const DEFAULT_LIQUIDATION_BONUS: u64 = 500;
+pub const DEFAULT_MAX_LIQUIDATION_BONUS: u64 = 1_000;
+pub const DEFAULT_LIQUIDATION_PROTOCOL_FEE: u64 = 1_000;
+/// Health shortfall below 1.0, in bps, at which the liquidation bonus reaches its maximum.
+pub const BONUS_FULL_SHORTFALL: u64 = 1_000;
+pub const DEFAULT_CLOSE_FACTOR: u64 = 5_000;
+/// Annual borrow rate, in bps, for new banks.
+pub const DEFAULT_INTEREST_RATE: u64 = 500;
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+/// Virtual assets and shares added to every deposit conversion, as if this much had been
+/// deposited 1:1 and locked forever. Inflating the share price then mostly benefits the
+/// virtual position, so a first depositor can't round later deposits down to nothing.
+pub const VIRTUAL_DEPOSIT: u64 = 1_000_000;
+/// Reward programs a single bank can run at once.
+pub const MAX_REWARDS: usize = 2;
+/// Fixed-point scale of `RewardInfo::index`, reward tokens per share.
+pub const REWARD_INDEX_SCALE: u128 = 1_000_000_000_000;
+/// Passing this as the amount to `repay` or `withdraw` settles the whole position.
+#[constant]
+pub const SETTLE_ALL: u64 = u64::MAX;
+
+/// Layout version of `Bank` and `User` written on init and by `migrate_account`.
+/// Accounts created before versioning have no version byte and are v1.
+pub const ACCOUNT_VERSION: u8 = 2;
+
+/// Zeroed bytes at the end of `Bank` and `User`, new fields are carved out of them so
+/// existing accounts keep their size.
+pub const BANK_RESERVED: usize = 128;
+pub const USER_RESERVED: usize = 48;
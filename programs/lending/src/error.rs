@@ -6,4 +6,62 @@ pub enum ErrorCode {
     InsufficientFunds,
     #[msg("Over borrowable Amount")]
     OverBorrowableAmount,
+    #[msg("Signer is not allowed to act on this obligation")]
+    Unauthorized,
+    #[msg("Math operation overflowed")]
+    MathOverflow,
+    #[msg("Amount is too small to mint or burn any shares")]
+    ZeroShares,
+    #[msg("Oracle price is older than the maximum allowed age")]
+    StaleOracle,
+    #[msg("Oracle account or price is invalid")]
+    InvalidOracle,
+    #[msg("Bank is paused")]
+    BankPaused,
+    #[msg("Bank deposit or borrow cap exceeded")]
+    CapExceeded,
+    #[msg("Obligation would be unhealthy after this operation")]
+    Unhealthy,
+    #[msg("Obligation is not undercollateralized")]
+    NotUndercollateralized,
+    #[msg("Mint does not belong to this obligation")]
+    InvalidMint,
+    #[msg("Invalid bank configuration")]
+    InvalidConfig,
+    #[msg("Liquidation amount exceeds the close factor")]
+    LiquidationTooLarge,
+    #[msg("Liquidation did not improve the obligation's health")]
+    LiquidationNotHealthier,
+    #[msg("Liquidation pushes the obligation past the target health")]
+    OverLiquidated,
+    #[msg("Obligation still has collateral or no debt to write off")]
+    NoBadDebt,
+    #[msg("Obligation must be refreshed in the same slot")]
+    StaleObligation,
+    #[msg("Borrow is below the bank's minimum")]
+    BelowMinimumBorrow,
+    #[msg("Remaining debt would be below the bank's minimum")]
+    DebtBelowMinimum,
+    #[msg("Claim this leg's rewards before moving it to another bank")]
+    UnclaimedRewards,
+    #[msg("Reward program not found on this bank")]
+    RewardNotFound,
+    #[msg("Fixed-term borrowing is disabled or the term is out of range")]
+    FixedTermUnavailable,
+    #[msg("This leg already has an open fixed-term loan")]
+    FixedLoanActive,
+    #[msg("No fixed-term loan on this leg")]
+    NoFixedLoan,
+    #[msg("Swap returned less than the minimum requested")]
+    SlippageExceeded,
+    #[msg("start_batch must be a top-level instruction followed by end_batch for the same obligation")]
+    InvalidBatch,
+    #[msg("Obligation is already in a batch")]
+    BatchActive,
+    #[msg("Obligation is not in a batch")]
+    NoBatch,
+    #[msg("Account is not a v1 Bank or User")]
+    NotMigratable,
+    #[msg("A token account needed to pay out this instruction was not passed")]
+    MissingTokenAccount,
 }
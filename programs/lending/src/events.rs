@@ -0,0 +1,190 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct DepositEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares_minted: u64,
+    pub user_deposit: u64,
+    pub user_deposit_share: u64,
+    pub bank_total_deposit: u64,
+    pub bank_total_deposit_share: u64,
+}
+
+#[event]
+pub struct WithdrawEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares_burned: u64,
+    pub user_deposit: u64,
+    pub user_deposit_share: u64,
+    pub bank_total_deposit: u64,
+    pub bank_total_deposit_share: u64,
+    /// Oracle prices of `mint` and of the other mint valued with it, the obligation's other leg
+    /// or what `swap_collateral` swaps into, as `price * 10^exponent`.
+    pub price: i64,
+    pub price_exponent: i32,
+    pub other_price: i64,
+    pub other_price_exponent: i32,
+}
+
+#[event]
+pub struct BorrowEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares_minted: u64,
+    pub user_borrow: u64,
+    pub user_borrow_share: u64,
+    pub bank_total_borrow: u64,
+    pub bank_total_borrow_share: u64,
+    /// Oracle prices the health check used, as `price * 10^exponent`.
+    pub collateral_price: i64,
+    pub collateral_price_exponent: i32,
+    pub borrowed_price: i64,
+    pub borrowed_price_exponent: i32,
+    /// Origination fee added to the debt on top of `amount`.
+    pub fee: u64,
+    /// Part of `fee` paid to the referrer, the rest is kept as protocol fees.
+    pub referrer_fee: u64,
+    /// Locked annual rate in bps and due date of a fixed-term borrow, zero for variable-rate.
+    /// `user_borrow` and `user_borrow_share` always describe the variable-rate position.
+    pub fixed_rate: u64,
+    pub maturity: i64,
+}
+
+#[event]
+pub struct RepayEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares_burned: u64,
+    pub user_borrow: u64,
+    pub user_borrow_share: u64,
+    pub bank_total_borrow: u64,
+    pub bank_total_borrow_share: u64,
+}
+
+#[event]
+pub struct RepayFixedEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub interest_paid: u64,
+    pub principal_paid: u64,
+    /// Principal and interest still owed on the loan.
+    pub remaining: u64,
+    pub bank_total_fixed_borrow: u64,
+}
+
+#[event]
+pub struct LiquidateEvent {
+    pub liquidator: Pubkey,
+    pub user: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub borrowed_bank: Pubkey,
+    pub borrowed_mint: Pubkey,
+    pub debt_repaid: u64,
+    pub collateral_seized: u64,
+    pub bonus: u64,
+    pub bonus_bps: u64,
+    /// Part of the bonus kept by the collateral bank as protocol fees.
+    pub protocol_fee: u64,
+    /// Collateral actually sent to the liquidator, `collateral_seized - protocol_fee`.
+    pub liquidator_collateral: u64,
+    /// Deposit shares credited to the liquidator's obligation, zero when paid out in tokens.
+    pub shares_to_liquidator: u64,
+    /// Oracle prices the liquidation used, as `price * 10^exponent`.
+    pub collateral_price: i64,
+    pub collateral_price_exponent: i32,
+    pub borrowed_price: i64,
+    pub borrowed_price_exponent: i32,
+    /// Health factors in bps, `u64::MAX` once the obligation has no debt.
+    pub health_before: u64,
+    pub health_after: u64,
+    /// Whether the fixed-term loan was repaid rather than the variable-rate debt.
+    pub fixed_term: bool,
+}
+
+#[event]
+pub struct ClaimRewardsEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+    /// Rewards still owed because the vault ran short.
+    pub remaining: u64,
+}
+
+#[event]
+pub struct LeverageEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub borrowed_bank: Pubkey,
+    /// Collateral deposited from the signer before levering up.
+    pub deposited: u64,
+    /// Borrowed tokens sent to the swap, the origination fee comes on top.
+    pub borrowed: u64,
+    pub fee: u64,
+    /// Collateral received from the swap and deposited.
+    pub swapped_out: u64,
+    pub health_after: u64,
+}
+
+#[event]
+pub struct SwapCollateralEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub from_bank: Pubkey,
+    pub to_bank: Pubkey,
+    /// Collateral withdrawn from `from_bank` and sent to the swap.
+    pub withdrawn: u64,
+    /// Collateral received from the swap and deposited into `to_bank`.
+    pub swapped_out: u64,
+    pub health_after: u64,
+}
+
+#[event]
+pub struct RefinanceEvent {
+    pub signer: Pubkey,
+    pub user: Pubkey,
+    pub source_bank: Pubkey,
+    pub target_bank: Pubkey,
+    /// Borrowed from `target_bank` and sent to the swap, the origination fee comes on top.
+    pub borrowed: u64,
+    pub fee: u64,
+    /// Source tokens received from the swap.
+    pub swapped_out: u64,
+    /// Debt repaid to `source_bank`, any surplus from the swap stays with the signer.
+    pub repaid: u64,
+    pub health_after: u64,
+}
+
+#[event]
+pub struct BadDebtEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub debt_written_off: u64,
+    pub shares_burned: u64,
+    pub covered_by_protocol_fees: u64,
+    pub socialized: u64,
+    pub bank_total_deposit: u64,
+    pub bank_total_deposit_share: u64,
+    pub bank_protocol_fees: u64,
+}
@@ -0,0 +1,182 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::Price;
+
+use crate::constants::{BPS, VALUE_DECIMALS};
+use crate::error::ErrorCode;
+use crate::{Bank, User};
+
+/// One of an obligation's positions in a single bank, priced at `price`.
+#[derive(Clone, Copy)]
+pub struct Leg<'a> {
+    pub bank: &'a Bank,
+    pub price: Price,
+    pub decimals: u8,
+    pub deposit_share: u64,
+    pub borrow_share: u64,
+    /// Fixed-term debt as of the loan's last accrual.
+    pub fixed_debt: u64,
+}
+
+impl<'a> Leg<'a> {
+    pub fn new(bank: &'a Bank, price: Price, decimals: u8, user: &User) -> Self {
+        Leg {
+            bank,
+            price,
+            decimals,
+            deposit_share: user.deposit_share(&bank.mint_address),
+            borrow_share: user.borrow_share(&bank.mint_address),
+            fixed_debt: user.fixed_loan(&bank.mint_address).owed(),
+        }
+    }
+}
+
+/// USD totals for an obligation, all in `VALUE_DECIMALS`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Health {
+    pub collateral_value: u128,
+    /// Collateral weighted by each bank's liquidation threshold.
+    pub liquidation_value: u128,
+    /// Collateral weighted by each bank's max LTV.
+    pub borrow_limit: u128,
+    pub debt_value: u128,
+}
+
+impl Health {
+    pub fn compute(legs: &[Leg]) -> Result<Self> {
+        let mut health = Health::default();
+        for leg in legs {
+            let deposited = leg.bank.deposit_shares_to_amount(leg.deposit_share)?;
+            let borrowed = leg.bank.borrow_shares_to_amount(leg.borrow_share)?
+                .checked_add(leg.fixed_debt)
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            let collateral = token_value(deposited, &leg.price, leg.decimals, false)?;
+            let debt = token_value(borrowed, &leg.price, leg.decimals, true)?;
+
+            health.collateral_value = add(health.collateral_value, collateral)?;
+            health.liquidation_value = add(health.liquidation_value, apply_bps(collateral, leg.bank.liquity_threshold)?)?;
+            health.borrow_limit = add(health.borrow_limit, apply_bps(collateral, leg.bank.max_ltv)?)?;
+            health.debt_value = add(health.debt_value, debt)?;
+        }
+        Ok(health)
+    }
+
+    /// `liquidation_value / debt_value` in bps, `u64::MAX` when there is no debt.
+    pub fn health_factor(&self) -> u64 {
+        if self.debt_value == 0 {
+            return u64::MAX;
+        }
+        let factor = self.liquidation_value.saturating_mul(BPS as u128) / self.debt_value;
+        u64::try_from(factor).unwrap_or(u64::MAX)
+    }
+
+    pub fn is_liquidatable(&self) -> bool {
+        self.debt_value > 0 && self.liquidation_value < self.debt_value
+    }
+
+    pub fn within_borrow_limit(&self) -> bool {
+        self.debt_value <= self.borrow_limit
+    }
+
+    /// Additional debt value the obligation can take on before hitting its borrow limit.
+    pub fn borrow_headroom(&self) -> u128 {
+        self.borrow_limit.saturating_sub(self.debt_value)
+    }
+}
+
+/// USD value of `amount` native units, in `VALUE_DECIMALS`.
+pub fn token_value(amount: u64, price: &Price, decimals: u8, round_up: bool) -> Result<u128> {
+    let raw = (amount as u128)
+        .checked_mul(price.price as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let exponent = VALUE_DECIMALS as i32 + price.exponent - decimals as i32;
+    if exponent >= 0 {
+        raw.checked_mul(pow10(exponent as u32)?).ok_or(ErrorCode::MathOverflow.into())
+    } else {
+        div(raw, pow10(exponent.unsigned_abs())?, round_up)
+    }
+}
+
+/// Native units worth `value` (in `VALUE_DECIMALS`), rounded down unless `round_up`.
+pub fn value_to_amount(value: u128, price: &Price, decimals: u8, round_up: bool) -> Result<u64> {
+    let exponent = decimals as i32 - VALUE_DECIMALS as i32 - price.exponent;
+    let scaled = if exponent >= 0 {
+        value.checked_mul(pow10(exponent as u32)?).ok_or(ErrorCode::MathOverflow)?
+    } else {
+        div(value, pow10(exponent.unsigned_abs())?, round_up)?
+    };
+    let amount = div(scaled, price.price as u128, round_up)?;
+    u64::try_from(amount).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+pub fn apply_bps(value: u128, bps: u64) -> Result<u128> {
+    value
+        .checked_mul(bps as u128)
+        .map(|v| v / BPS as u128)
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+fn add(a: u128, b: u128) -> Result<u128> {
+    a.checked_add(b).ok_or(ErrorCode::MathOverflow.into())
+}
+
+fn div(numerator: u128, denominator: u128, round_up: bool) -> Result<u128> {
+    require!(denominator > 0, ErrorCode::MathOverflow);
+    let quotient = numerator / denominator;
+    if round_up && quotient * denominator != numerator {
+        return add(quotient, 1);
+    }
+    Ok(quotient)
+}
+
+fn pow10(exponent: u32) -> Result<u128> {
+    10u128.checked_pow(exponent).ok_or(ErrorCode::MathOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{bank, price, user};
+
+    #[test]
+    fn compute_weights_collateral_and_sums_debt() {
+        let (usdc_mint, sol_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let usdc_bank = bank(usdc_mint, 8_000, 7_500);
+        let sol_bank = bank(sol_mint, 8_500, 8_000);
+        let mut user = user(usdc_mint, sol_mint);
+        // 10 SOL at $100 against 400 USDC borrowed and 100 USDC fixed.
+        user.deposit_sol_share = 10_000_000_000;
+        user.borrow_usdc_share = 400_000_000;
+        user.fixed_usdc.principal = 100_000_000;
+
+        let health = Health::compute(&[
+            Leg::new(&usdc_bank, price(100_000_000, -8), 6, &user),
+            Leg::new(&sol_bank, price(10_000_000_000, -8), 9, &user),
+        ])
+        .unwrap();
+        assert_eq!(health.collateral_value, 1_000_000_000);
+        assert_eq!(health.liquidation_value, 850_000_000);
+        assert_eq!(health.borrow_limit, 800_000_000);
+        assert_eq!(health.debt_value, 500_000_000);
+        assert_eq!(health.health_factor(), 17_000);
+        assert_eq!(health.borrow_headroom(), 300_000_000);
+        assert!(health.within_borrow_limit());
+        assert!(!health.is_liquidatable());
+    }
+
+    #[test]
+    fn debt_free_obligation_is_infinitely_healthy() {
+        let health = Health { collateral_value: 1, liquidation_value: 1, borrow_limit: 1, debt_value: 0 };
+        assert_eq!(health.health_factor(), u64::MAX);
+        assert!(!health.is_liquidatable());
+    }
+
+    #[test]
+    fn token_value_rounds_debt_up_and_collateral_down() {
+        // One lamport at $100 is a fraction of a micro-dollar.
+        let sol = price(10_000_000_000, -8);
+        assert_eq!(token_value(1, &sol, 9, false).unwrap(), 0);
+        assert_eq!(token_value(1, &sol, 9, true).unwrap(), 1);
+        assert_eq!(value_to_amount(100_000_000, &sol, 9, false).unwrap(), 1_000_000_000);
+    }
+}
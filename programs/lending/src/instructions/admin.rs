@@ -1,7 +1,12 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::{Bank, User};
+use crate::error::ErrorCode;
+use crate::program::Lending;
+use crate::{
+    Bank, User, ACCOUNT_VERSION, DEFAULT_CLOSE_FACTOR, DEFAULT_INTEREST_RATE, DEFAULT_LIQUIDATION_BONUS,
+    DEFAULT_LIQUIDATION_PROTOCOL_FEE, DEFAULT_MAX_LIQUIDATION_BONUS,
+};
 
 #[derive(Accounts)]
 pub struct InitBank<'info> {
@@ -28,11 +33,18 @@ pub struct InitBank<'info> {
         bump
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub program: Program<'info, Lending>,
+
+    /// Required whenever the program is upgradeable, its upgrade authority must sign.
+    pub program_data: Option<Account<'info, ProgramData>>,
+
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>
 }
 
 #[derive(Accounts)]
+#[instruction(index: u8)]
 pub struct InitUser<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
@@ -41,30 +53,152 @@ pub struct InitUser<'info> {
         init,
         payer=signer,
         space=8+User::INIT_SPACE,
-        seeds=[b"user", signer.key().as_ref()],
+        seeds=[b"user", signer.key().as_ref(), &[index]],
         bump,
     )]
     pub user_account: Account<'info, User>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateBank<'info> {
+    pub authority: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds= [b"bank", mint.key().as_ref()],
+        bump,
+        has_one = authority,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+/// Bank settings the authority can change after initialization, `None` leaves a field untouched.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct BankConfigParams {
+    pub paused: Option<bool>,
+    pub deposit_cap: Option<u64>,
+    pub borrow_cap: Option<u64>,
+    pub liquidation_threshold: Option<u64>,
+    pub max_ltv: Option<u64>,
+    pub liquidation_bonus: Option<u64>,
+    pub liquidation_max_bonus: Option<u64>,
+    pub liquidation_bonus_ramp: Option<u64>,
+    pub liquidation_protocol_fee: Option<u64>,
+    pub close_factor: Option<u64>,
+    pub interest_rate: Option<u64>,
+    pub min_borrow: Option<u64>,
+    pub min_debt: Option<u64>,
+    pub borrow_fee: Option<u64>,
+    pub referrer_fee_share: Option<u64>,
+    pub fixed_rate_premium: Option<u64>,
+    pub max_fixed_term: Option<u64>,
+    pub fixed_grace_period: Option<u64>,
+    pub oracle_feed_id: Option<[u8; 32]>,
+}
+
 impl <'info>InitBank<'info> {
-    pub fn process_init_bank(&mut self, liquidation_threshold:u64, max_ltv:u64 )-> Result<()>{
+    /// A bank's oracle and risk parameters decide what every obligation can borrow against it,
+    /// so on an upgradeable deployment only the upgrade authority may list one.
+    pub fn process_init_bank(&mut self, liquidation_threshold:u64, max_ltv:u64, oracle_feed_id: [u8; 32])-> Result<()>{
+        if let Some(program_data_address) = self.program.programdata_address()? {
+            let program_data = self.program_data.as_ref().ok_or(ErrorCode::Unauthorized)?;
+            require_keys_eq!(program_data.key(), program_data_address, ErrorCode::Unauthorized);
+            require!(program_data.upgrade_authority_address == Some(self.signer.key()), ErrorCode::Unauthorized);
+        }
+
         let bank = &mut self.bank;
+        bank.oracle_feed_id = oracle_feed_id;
         bank.mint_address = self.mint.key();
         bank.authority = self.signer.key();
         bank.liquity_threshold =liquidation_threshold;
         bank.max_ltv = max_ltv;
-        bank.interest_rate = 0.05 as u64;
-        Ok(())
+        bank.liquity_bonus = DEFAULT_LIQUIDATION_BONUS;
+        bank.liquity_max_bonus = DEFAULT_MAX_LIQUIDATION_BONUS;
+        bank.liquity_protocol_fee = DEFAULT_LIQUIDATION_PROTOCOL_FEE;
+        bank.liquity_close_factor = DEFAULT_CLOSE_FACTOR;
+        bank.interest_rate = DEFAULT_INTEREST_RATE;
+        bank.last_updated = Clock::get()?.unix_timestamp as u64;
+        bank.version = ACCOUNT_VERSION;
+        bank.validate_config()
+    }
+}
+
+impl <'info>UpdateBank<'info> {
+    pub fn process_update_bank(&mut self, params: BankConfigParams) -> Result<()> {
+        let bank = &mut self.bank;
+        // Interest up to now is owed at the old rate.
+        bank.accrue_interest(Clock::get()?.unix_timestamp)?;
+        if let Some(paused) = params.paused {
+            bank.paused = paused;
+        }
+        if let Some(deposit_cap) = params.deposit_cap {
+            bank.deposit_cap = deposit_cap;
+        }
+        if let Some(borrow_cap) = params.borrow_cap {
+            bank.borrow_cap = borrow_cap;
+        }
+        if let Some(liquidation_threshold) = params.liquidation_threshold {
+            bank.liquity_threshold = liquidation_threshold;
+        }
+        if let Some(max_ltv) = params.max_ltv {
+            bank.max_ltv = max_ltv;
+        }
+        if let Some(liquidation_bonus) = params.liquidation_bonus {
+            bank.liquity_bonus = liquidation_bonus;
+        }
+        if let Some(liquidation_max_bonus) = params.liquidation_max_bonus {
+            bank.liquity_max_bonus = liquidation_max_bonus;
+        }
+        if let Some(liquidation_bonus_ramp) = params.liquidation_bonus_ramp {
+            bank.liquity_bonus_ramp = liquidation_bonus_ramp;
+        }
+        if let Some(liquidation_protocol_fee) = params.liquidation_protocol_fee {
+            bank.liquity_protocol_fee = liquidation_protocol_fee;
+        }
+        if let Some(close_factor) = params.close_factor {
+            bank.liquity_close_factor = close_factor;
+        }
+        if let Some(interest_rate) = params.interest_rate {
+            bank.interest_rate = interest_rate;
+        }
+        if let Some(min_borrow) = params.min_borrow {
+            bank.min_borrow = min_borrow;
+        }
+        if let Some(min_debt) = params.min_debt {
+            bank.min_debt = min_debt;
+        }
+        if let Some(borrow_fee) = params.borrow_fee {
+            bank.borrow_fee = borrow_fee;
+        }
+        if let Some(referrer_fee_share) = params.referrer_fee_share {
+            bank.referrer_fee_share = referrer_fee_share;
+        }
+        if let Some(fixed_rate_premium) = params.fixed_rate_premium {
+            bank.fixed_rate_premium = fixed_rate_premium;
+        }
+        if let Some(max_fixed_term) = params.max_fixed_term {
+            bank.max_fixed_term = max_fixed_term;
+        }
+        if let Some(fixed_grace_period) = params.fixed_grace_period {
+            bank.fixed_grace_period = fixed_grace_period;
+        }
+        if let Some(oracle_feed_id) = params.oracle_feed_id {
+            bank.oracle_feed_id = oracle_feed_id;
+        }
+        bank.validate_config()
     }
 }
 
 impl <'info>InitUser<'info> {
-    pub fn process_init_user(&mut self, usdc_address: Pubkey)->Result<()>{
+    pub fn process_init_user(&mut self, index: u8, usdc_address: Pubkey)->Result<()>{
         let user_account = &mut self.user_account;
         user_account.owner = self.signer.key();
+        user_account.index = index;
         user_account.usdc_address = usdc_address;
+        user_account.version = ACCOUNT_VERSION;
         Ok(())
     }
 }
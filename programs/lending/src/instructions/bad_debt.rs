@@ -0,0 +1,93 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::error::ErrorCode;
+use crate::events::BadDebtEvent;
+use crate::{Bank, FixedLoan, User};
+
+#[derive(Accounts)]
+pub struct HandleBadDebt<'info> {
+    pub authority: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump,
+        has_one = authority,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.accepts_mint(&mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+impl <'info>HandleBadDebt<'info> {
+    /// Writes off the obligation's remaining debt in this bank once it has no collateral left.
+    /// The loss is paid from `protocol_fees` first and whatever remains lowers the value of
+    /// every deposit share.
+    pub fn process_handle_bad_debt(&mut self) -> Result<()> {
+        let mint = self.mint.key();
+        let bank = &mut self.bank;
+        let user = &mut self.user_account;
+        let now = Clock::get()?.unix_timestamp;
+        bank.accrue_interest(now)?;
+        user.settle_rewards(bank, now)?;
+
+        let shares = user.borrow_share(&mint);
+        let fixed_loan = *user.fixed_loan(&mint);
+        require!(!user.has_collateral() && (shares > 0 || fixed_loan.is_active()), ErrorCode::NoBadDebt);
+
+        let variable_debt = bank.borrow_shares_to_amount(shares)?.min(bank.total_borrow);
+        // Unpaid fixed-term interest was never credited to depositors, so only the principal is a loss.
+        let fixed_principal = fixed_loan.principal.min(bank.total_fixed_borrow);
+        *user.fixed_loan_mut(&mint) = FixedLoan::default();
+        let debt = variable_debt.checked_add(fixed_principal).ok_or(ErrorCode::MathOverflow)?;
+
+        match mint {
+            key if key == user.usdc_address => {
+                user.borrow_usdc = 0;
+                user.borrow_usdc_share = 0;
+            }
+            _ => {
+                user.borrow_sol = 0;
+                user.borrow_sol_share = 0;
+            }
+        }
+        if user.borrow_usdc_share == 0 && user.borrow_sol_share == 0 && !user.fixed_usdc.is_active() && !user.fixed_sol.is_active() {
+            user.liquidatable_since = 0;
+        }
+
+        bank.total_borrow = bank.total_borrow.checked_sub(variable_debt).ok_or(ErrorCode::MathOverflow)?;
+        bank.total_fixed_borrow = bank.total_fixed_borrow.checked_sub(fixed_principal).ok_or(ErrorCode::MathOverflow)?;
+        bank.total_borrow_share = bank.total_borrow_share.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+
+        let covered = debt.min(bank.protocol_fees);
+        bank.protocol_fees = bank.protocol_fees.checked_sub(covered).ok_or(ErrorCode::MathOverflow)?;
+
+        // A loss larger than every deposit can only wipe the deposits out, it must not block the write-off.
+        let socialized = debt.checked_sub(covered).ok_or(ErrorCode::MathOverflow)?.min(bank.total_deposit);
+        bank.total_deposit = bank.total_deposit.checked_sub(socialized).ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(BadDebtEvent {
+            user: user.key(),
+            bank: bank.key(),
+            mint,
+            debt_written_off: debt,
+            shares_burned: shares,
+            covered_by_protocol_fees: covered,
+            socialized,
+            bank_total_deposit: bank.total_deposit,
+            bank_total_deposit_share: bank.total_deposit_share,
+            bank_protocol_fees: bank.protocol_fees,
+        });
+
+        Ok(())
+    }
+}
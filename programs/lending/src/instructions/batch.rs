@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{get_instruction_relative, load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::error::ErrorCode;
+use crate::health::{Health, Leg};
+use crate::oracle::get_price;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct StartBatch<'info> {
+    pub signer: Signer<'info>,
+
+    /// A batch lets borrows and withdrawals skip their health checks, so it takes borrow rights.
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.can_borrow(&signer.key()) @ ErrorCode::Unauthorized,
+    )]
+    pub user_account: Account<'info, User>,
+
+    /// CHECK: the instructions sysvar, checked by address.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EndBatch<'info> {
+    /// Must stay the first account, `start_batch` looks for the obligation at this position.
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.spans_both_legs(&mint.key(), &other_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    /// Mint of the obligation's other leg.
+    pub other_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", other_mint.key().as_ref()],
+        bump
+    )]
+    pub other_bank: Account<'info, Bank>,
+
+    pub other_price_update: Account<'info, PriceUpdateV2>,
+}
+
+impl <'info>StartBatch<'info> {
+    /// Opens a batch on the obligation. Only allowed as a top-level instruction that a later
+    /// `end_batch` on the same obligation follows in this transaction, so the batch can
+    /// never outlive the transaction.
+    pub fn process_start_batch(&mut self) -> Result<()> {
+        require!(!self.user_account.in_batch, ErrorCode::BatchActive);
+
+        let sysvar = self.instructions.to_account_info();
+        require!(get_instruction_relative(0, &sysvar)?.program_id == crate::ID, ErrorCode::InvalidBatch);
+
+        let user_key = self.user_account.key();
+        let mut index = load_current_index_checked(&sysvar)? as usize + 1;
+        let mut closed = false;
+        while let Ok(ix) = load_instruction_at_checked(index, &sysvar) {
+            if ix.program_id == crate::ID
+                && ix.data.get(..8) == Some(&crate::instruction::EndBatch::DISCRIMINATOR[..])
+                && ix.accounts.first().map(|meta| meta.pubkey) == Some(user_key)
+            {
+                closed = true;
+                break;
+            }
+            index += 1;
+        }
+        require!(closed, ErrorCode::InvalidBatch);
+
+        self.user_account.in_batch = true;
+        Ok(())
+    }
+}
+
+impl <'info>EndBatch<'info> {
+    /// Closes the batch, failing the transaction unless the obligation ends within its borrow limit.
+    pub fn process_end_batch(&mut self) -> Result<()> {
+        require!(self.user_account.in_batch, ErrorCode::NoBatch);
+
+        let now = Clock::get()?.unix_timestamp;
+        self.bank.accrue_interest(now)?;
+        self.other_bank.accrue_interest(now)?;
+
+        let price = get_price(&self.price_update, &self.bank.oracle_feed_id)?;
+        let other_price = get_price(&self.other_price_update, &self.other_bank.oracle_feed_id)?;
+
+        let health = Health::compute(&[
+            Leg::new(&self.bank, price, self.mint.decimals, &self.user_account),
+            Leg::new(&self.other_bank, other_price, self.other_mint.decimals, &self.user_account),
+        ])?;
+        require!(health.within_borrow_limit(), ErrorCode::Unhealthy);
+
+        self.user_account.in_batch = false;
+        Ok(())
+    }
+}
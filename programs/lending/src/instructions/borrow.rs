@@ -1,15 +1,15 @@
-use std::f32::consts::E;
-
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken, 
     token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, transfer_checked}
 };
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 
-use crate::{state::*, USDC_USD_FEED_ID};
-use crate::constants::{MAXIMUM_AGE, SOL_USD_FEED_ID};
+use crate::state::*;
 use crate::error::ErrorCode;
+use crate::events::BorrowEvent;
+use crate::health::{apply_bps, Health, Leg};
+use crate::oracle::get_price;
 
 #[derive(Accounts)]
 pub struct Borrow<'info> {
@@ -32,23 +32,49 @@ pub struct Borrow<'info> {
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    /// Mint of the obligation's other leg, whose deposits back this borrow.
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
     #[account(
-        mut,
-        seeds=[b"user", signer.key().as_ref()],
+        seeds=[b"bank", collateral_mint.key().as_ref()],
         bump
     )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.can_borrow(&signer.key()) @ ErrorCode::Unauthorized,
+        constraint = user_account.spans_both_legs(&mint.key(), &collateral_mint.key()) @ ErrorCode::InvalidMint,
+    )]
     pub user_account: Account<'info, User>,
 
+    /// Borrowed funds always go to the obligation owner, even when a delegate signs.
+    #[account(address = user_account.owner)]
+    pub owner: SystemAccount<'info>,
+
     #[account(
         init_if_needed,
         payer=signer,
         associated_token::mint=mint,
-        associated_token::authority=signer,
+        associated_token::authority=owner,
         associated_token::token_program=token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    /// Integrator that routed this borrow, paid `referrer_fee_share` of the origination fee.
+    #[account(
+        mut,
+        token::mint=mint,
+        token::token_program=token_program,
+        constraint = referrer_token_account.owner != user_account.owner @ ErrorCode::Unauthorized,
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Price of the collateral mint.
     pub price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -56,30 +82,69 @@ pub struct Borrow<'info> {
 
 impl <'info>Borrow<'info> {
     pub fn process_borrow(&mut self, amount: u64, bumps: &BorrowBumps)->Result<()>{
+        self.borrow(amount, None, bumps)
+    }
+
+    /// Borrows at a rate locked now from the bank's rate plus `fixed_rate_premium`, due in `term` seconds.
+    pub fn process_borrow_fixed(&mut self, amount: u64, term: u64, bumps: &BorrowBumps)->Result<()>{
+        self.borrow(amount, Some(term), bumps)
+    }
+
+    fn borrow(&mut self, amount: u64, term: Option<u64>, bumps: &BorrowBumps)->Result<()>{
+        require!(!self.bank.paused, ErrorCode::BankPaused);
+        self.user_account.require_fresh()?;
+        let now = Clock::get()?.unix_timestamp;
+        self.bank.accrue_interest(now)?;
+        require!(amount >= self.bank.min_borrow, ErrorCode::BelowMinimumBorrow);
+
+        let fee = self.bank.origination_fee(amount)?;
+        let referrer_fee = match self.referrer_token_account {
+            Some(_) => u64::try_from(apply_bps(fee as u128, self.bank.referrer_fee_share)?).map_err(|_| ErrorCode::MathOverflow)?,
+            None => 0,
+        };
+        let debt = amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        self.bank.check_borrow_cap(debt)?;
+
+        let mint_key = self.mint.key();
+        let collateral_price = get_price(&self.price_update, &self.collateral_bank.oracle_feed_id)?;
+        let borrowed_price = get_price(&self.borrowed_price_update, &self.bank.oracle_feed_id)?;
+
         let bank = &mut self.bank;
         let user = &mut self.user_account;
+        user.bind_mint(bank)?;
+        user.settle_rewards(bank, now)?;
+
+        bank.protocol_fees = bank.protocol_fees.checked_add(fee - referrer_fee).ok_or(ErrorCode::MathOverflow)?;
+
+        let (user_shares, fixed_rate, maturity) = match term {
+            None => (settle_borrow(bank, user, mint_key, debt)?, 0, 0),
+            Some(term) => {
+                require!(term > 0 && term <= bank.max_fixed_term, ErrorCode::FixedTermUnavailable);
+                let maturity = now.checked_add(term as i64).ok_or(ErrorCode::MathOverflow)?;
+                let rate = bank.fixed_rate()?;
+
+                let loan = user.fixed_loan_mut(&mint_key);
+                require!(!loan.is_active(), ErrorCode::FixedLoanActive);
+                *loan = FixedLoan { principal: debt, interest: 0, rate, last_accrued: now, maturity, interest_remainder: 0 };
+                bank.check_min_debt_amount(debt)?;
 
-        let price_update = &mut self.price_update;
-
-        let total_collateral: u64;
-
-        match self.mint.to_account_info().key() {
-            key if key == user.usdc_address => {
-                let sol_feed_id = get_feed_id_from_hex(SOL_USD_FEED_ID)?; 
-                let sol_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &sol_feed_id)?;
-                let accrued_interest = calculate_accrued_interest(user.deposit_sol, bank.interest_rate, user.last_updated)?;
-                total_collateral = sol_price.price as u64 * (user.deposit_sol + accrued_interest);
-            },
-            _ => {
-                let usdc_feed_id= get_feed_id_from_hex(USDC_USD_FEED_ID)?;
-                let usdc_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &usdc_feed_id)?;
-                total_collateral = usdc_price.price as u64 * user.deposit_usdc;
+                bank.total_fixed_borrow = bank.total_fixed_borrow.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+                (0, rate, maturity)
             }
-        }
+        };
+        user.last_updated_borrowed = now;
 
-        let borrowable_amount = total_collateral as u64 * bank.liquity_threshold;
+        let (user_borrow, user_borrow_share) = match mint_key {
+            key if key == user.usdc_address => (user.borrow_usdc, user.borrow_usdc_share),
+            _ => (user.borrow_sol, user.borrow_sol_share),
+        };
 
-        if borrowable_amount < amount {
+        let health = Health::compute(&[
+            Leg::new(bank, borrowed_price, self.mint.decimals, user),
+            Leg::new(&self.collateral_bank, collateral_price, self.collateral_mint.decimals, user),
+        ])?;
+        // Inside a batch `end_batch` checks the final state instead.
+        if !user.in_batch && !health.within_borrow_limit() {
             return  Err(ErrorCode::OverBorrowableAmount.into());
         }
 
@@ -91,7 +156,6 @@ impl <'info>Borrow<'info> {
         };
 
         let cpi_program = self.token_program.to_account_info();
-        let mint_key = self.mint.key();
 
         let signer_seeds: &[&[&[u8]]] = &[
             &[
@@ -101,38 +165,65 @@ impl <'info>Borrow<'info> {
             ],
         ];
 
-        let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+        let cpi_ctx = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts).with_signer(signer_seeds);
 
         transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
 
-        if bank.total_borrow == 0 {
-            bank.total_borrow = amount;
-            bank.total_borrow_share = amount;
+        if let Some(referrer_token_account) = &self.referrer_token_account {
+            if referrer_fee > 0 {
+                let transfer_to_referrer = TransferChecked{
+                    from: self.bank_token_account.to_account_info(),
+                    to: referrer_token_account.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    authority: self.bank_token_account.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(cpi_program, transfer_to_referrer).with_signer(signer_seeds);
+                transfer_checked(cpi_ctx, referrer_fee, self.mint.decimals)?;
+            }
         }
 
-        let borrow_ratio = amount.checked_div(bank.total_borrow).unwrap();
-        let user_shares = bank.total_borrow_share.checked_mul(borrow_ratio).unwrap();
+        emit!(BorrowEvent {
+            signer: self.signer.key(),
+            user: user.key(),
+            bank: bank.key(),
+            mint: mint_key,
+            amount,
+            shares_minted: user_shares,
+            user_borrow,
+            user_borrow_share,
+            bank_total_borrow: bank.total_borrow,
+            bank_total_borrow_share: bank.total_borrow_share,
+            collateral_price: collateral_price.price,
+            collateral_price_exponent: collateral_price.exponent,
+            borrowed_price: borrowed_price.price,
+            borrowed_price_exponent: borrowed_price.exponent,
+            fee,
+            referrer_fee,
+            fixed_rate,
+            maturity,
+        });
+        Ok(())
+    }
+}
 
-        bank.total_borrow += amount;
-        bank.total_borrow_share += user_shares;
+/// Mints borrow shares for `debt` on both the obligation and the bank.
+pub(crate) fn settle_borrow(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, debt: u64) -> Result<u64> {
+    let user_shares = bank.shares_for_borrow(debt)?;
+    require!(user_shares > 0, ErrorCode::ZeroShares);
 
-        match self.mint.to_account_info().key() {
-            key if key == user.usdc_address => {
-                user.borrow_usdc += amount;
-                user.borrow_usdc_share += user_shares;
-            }
-            _ => {
-                user.borrow_sol += amount;
-                user.borrow_sol_share += user_shares;
-            }
+    bank.total_borrow = bank.total_borrow.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_borrow_share = bank.total_borrow_share.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    match mint {
+        key if key == user.usdc_address => {
+            user.borrow_usdc = user.borrow_usdc.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+            user.borrow_usdc_share = user.borrow_usdc_share.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        }
+        _ => {
+            user.borrow_sol = user.borrow_sol.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+            user.borrow_sol_share = user.borrow_sol_share.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
         }
-        Ok(())
     }
+    bank.check_min_debt(user.borrow_share(&mint))?;
+    Ok(user_shares)
 }
-
-fn calculate_accrued_interest(deposited: u64, interest_rate: u64, last_update: i64) -> Result<u64>{
-    let current_time = Clock::get()?.unix_timestamp;
-    let time_elapsed = current_time - last_update;
-    let new_value = (deposited as f64 * E.powf(interest_rate as f32 * time_elapsed as f32) as f64) as u64;
-    Ok(new_value)
-}
\ No newline at end of file
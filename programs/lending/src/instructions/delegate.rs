@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::User;
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds=[b"user", owner.key().as_ref(), &[user_account.index]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+impl <'info>SetDelegate<'info> {
+    /// Passing `Pubkey::default()` as the delegate revokes any existing delegation.
+    pub fn process_set_delegate(&mut self, delegate: Pubkey, can_borrow: bool, can_withdraw: bool) -> Result<()> {
+        let user = &mut self.user_account;
+        let revoked = delegate == Pubkey::default();
+
+        user.delegate = delegate;
+        user.delegate_can_borrow = can_borrow && !revoked;
+        user.delegate_can_withdraw = can_withdraw && !revoked;
+        Ok(())
+    }
+}
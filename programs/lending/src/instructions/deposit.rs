@@ -5,6 +5,8 @@ use anchor_spl::{
 };
 
 use crate::{Bank, User};
+use crate::events::DepositEvent;
+use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct Deposit<'info>{
@@ -29,8 +31,10 @@ pub struct Deposit<'info>{
 
     #[account(
         mut,
-        seeds=[b"user", signer.key().as_ref()],
-        bump
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.is_owner_or_delegate(&signer.key()) @ ErrorCode::Unauthorized,
+        constraint = user_account.accepts_mint(&mint.key()) @ ErrorCode::InvalidMint,
     )]
     pub user_account: Account<'info, User>,
 
@@ -49,6 +53,10 @@ pub struct Deposit<'info>{
 
 impl <'info>Deposit<'info> {
     pub fn process_deposit(&mut self, amount: u64)->Result<()>{
+        require!(!self.bank.paused, ErrorCode::BankPaused);
+        self.bank.accrue_interest(Clock::get()?.unix_timestamp)?;
+        self.bank.check_deposit_cap(amount)?;
+
         let transfer_cpi_accounts = TransferChecked{
             from: self.user_token_account.to_account_info(),
             to: self.bank_token_account.to_account_info(),
@@ -63,35 +71,49 @@ impl <'info>Deposit<'info> {
 
         transfer_checked(cpi_ctx, amount, decimals)?;
 
-        let bank = &mut self.bank;
-
-        if bank.total_deposit == 0 {
-            bank.total_deposit = amount;
-            bank.total_deposit_share = amount;
-        }
-
-        let deposit_ratio = amount.checked_div(bank.total_deposit).unwrap(); // 10
-        let user_share = bank.total_deposit_share.checked_mul(deposit_ratio).unwrap();
-
-        let user = &mut self.user_account;
-
-        match self.mint.to_account_info().key(){
-            key if key == user.usdc_address => {
-                user.deposit_usdc += amount;
-                user.deposit_usdc_share += user_share;
-            },
-            _ => {
-                user.deposit_sol += amount;
-                user.deposit_sol_share += user_share;
-            }
-        }
-
-        bank.total_deposit += amount;
-        bank.total_deposit_share += user_share;
-
-
-        user.last_updated = Clock::get()?.unix_timestamp;
+        let event = settle_deposit(&mut self.bank, &mut self.user_account, self.mint.key(), amount, self.signer.key())?;
+        emit!(event);
 
         Ok(())
     }
 }
+
+/// Mints deposit shares for `amount` on both the obligation and the bank.
+pub(crate) fn settle_deposit(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, amount: u64, signer: Pubkey) -> Result<DepositEvent> {
+    user.bind_mint(bank)?;
+    user.settle_rewards(bank, Clock::get()?.unix_timestamp)?;
+
+    let user_share = bank.shares_for_deposit(amount)?;
+    require!(user_share > 0, ErrorCode::ZeroShares);
+
+    let (user_deposit, user_deposit_share) = match mint {
+        key if key == user.usdc_address => {
+            user.deposit_usdc = user.deposit_usdc.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.deposit_usdc_share = user.deposit_usdc_share.checked_add(user_share).ok_or(ErrorCode::MathOverflow)?;
+            (user.deposit_usdc, user.deposit_usdc_share)
+        },
+        _ => {
+            user.deposit_sol = user.deposit_sol.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            user.deposit_sol_share = user.deposit_sol_share.checked_add(user_share).ok_or(ErrorCode::MathOverflow)?;
+            (user.deposit_sol, user.deposit_sol_share)
+        }
+    };
+
+    bank.total_deposit = bank.total_deposit.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_share = bank.total_deposit_share.checked_add(user_share).ok_or(ErrorCode::MathOverflow)?;
+
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    Ok(DepositEvent {
+        signer,
+        user: user.key(),
+        bank: bank.key(),
+        mint,
+        amount,
+        shares_minted: user_share,
+        user_deposit,
+        user_deposit_share,
+        bank_total_deposit: bank.total_deposit,
+        bank_total_deposit_share: bank.total_deposit_share,
+    })
+}
@@ -0,0 +1,190 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}
+};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::error::ErrorCode;
+use crate::events::LeverageEvent;
+use crate::health::{Health, Leg};
+use crate::instructions::borrow::settle_borrow;
+use crate::instructions::deposit::settle_deposit;
+use crate::oracle::get_price;
+use crate::swap::swap;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct Leverage<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", borrowed_mint.key().as_ref()],
+        bump
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", borrowed_mint.key().as_ref()],
+        bump
+    )]
+    pub borrowed_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Only the owner can lever up, the swap runs on their signature and token accounts.
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.owner == signer.key() @ ErrorCode::Unauthorized,
+        constraint = user_account.spans_both_legs(&collateral_mint.key(), &borrowed_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        mut,
+        associated_token::mint=collateral_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=borrowed_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_borrowed_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+
+    /// Adapter implementing `swap(amount_in, min_amount_out)`, its accounts follow as remaining accounts.
+    /// CHECK: any program may be used, proceeds are measured from `user_collateral_token_account`.
+    #[account(executable, constraint = swap_program.key() != crate::ID @ ErrorCode::InvalidConfig)]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>Leverage<'info> {
+    /// Deposits `deposit_amount` of collateral, borrows `borrow_amount`, swaps the borrowed
+    /// tokens into collateral through `swap_program` and deposits the proceeds. Health is only
+    /// checked once everything is in place, so the position can exceed what one borrow allows.
+    pub fn process_leverage(&mut self, deposit_amount: u64, borrow_amount: u64, min_amount_out: u64, bumps: &LeverageBumps, swap_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(!self.collateral_bank.paused && !self.borrowed_bank.paused, ErrorCode::BankPaused);
+        self.user_account.require_fresh()?;
+        let now = Clock::get()?.unix_timestamp;
+        self.collateral_bank.accrue_interest(now)?;
+        self.borrowed_bank.accrue_interest(now)?;
+
+        let borrowed_key = self.borrowed_mint.key();
+        let collateral_price = get_price(&self.collateral_price_update, &self.collateral_bank.oracle_feed_id)?;
+        let borrowed_price = get_price(&self.borrowed_price_update, &self.borrowed_bank.oracle_feed_id)?;
+
+        if deposit_amount > 0 {
+            self.deposit_collateral(deposit_amount)?;
+        }
+
+        let bank = &mut self.borrowed_bank;
+        require!(borrow_amount >= bank.min_borrow, ErrorCode::BelowMinimumBorrow);
+        let fee = bank.origination_fee(borrow_amount)?;
+        let debt = borrow_amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        bank.check_borrow_cap(debt)?;
+
+        let user = &mut self.user_account;
+        user.bind_mint(bank)?;
+        user.settle_rewards(bank, now)?;
+        bank.protocol_fees = bank.protocol_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        settle_borrow(bank, user, borrowed_key, debt)?;
+        user.last_updated_borrowed = now;
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.borrowed_bank_token_account.to_account_info(),
+            to: self.user_borrowed_token_account.to_account_info(),
+            mint: self.borrowed_mint.to_account_info(),
+            authority: self.borrowed_bank_token_account.to_account_info(),
+        };
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                b"treasury",
+                borrowed_key.as_ref(),
+                &[bumps.borrowed_bank_token_account]
+            ],
+        ];
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+        transfer_checked(cpi_ctx, borrow_amount, self.borrowed_mint.decimals)?;
+
+        // The initial deposit already moved tokens out of this account by CPI.
+        self.user_collateral_token_account.reload()?;
+        let balance_before = self.user_collateral_token_account.amount;
+        swap(&self.swap_program, swap_accounts, borrow_amount, min_amount_out)?;
+        self.user_collateral_token_account.reload()?;
+        let swapped_out = self.user_collateral_token_account.amount.saturating_sub(balance_before);
+        require!(swapped_out >= min_amount_out, ErrorCode::SlippageExceeded);
+
+        self.deposit_collateral(swapped_out)?;
+
+        let health = Health::compute(&[
+            Leg::new(&self.collateral_bank, collateral_price, self.collateral_mint.decimals, &self.user_account),
+            Leg::new(&self.borrowed_bank, borrowed_price, self.borrowed_mint.decimals, &self.user_account),
+        ])?;
+        if !health.within_borrow_limit() {
+            return Err(ErrorCode::OverBorrowableAmount.into());
+        }
+
+        emit!(LeverageEvent {
+            signer: self.signer.key(),
+            user: self.user_account.key(),
+            collateral_bank: self.collateral_bank.key(),
+            borrowed_bank: self.borrowed_bank.key(),
+            deposited: deposit_amount,
+            borrowed: borrow_amount,
+            fee,
+            swapped_out,
+            health_after: health.health_factor(),
+        });
+        Ok(())
+    }
+
+    fn deposit_collateral(&mut self, amount: u64) -> Result<()> {
+        self.collateral_bank.check_deposit_cap(amount)?;
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.user_collateral_token_account.to_account_info(),
+            to: self.collateral_bank_token_account.to_account_info(),
+            authority: self.signer.to_account_info(),
+            mint: self.collateral_mint.to_account_info()
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts);
+        transfer_checked(cpi_ctx, amount, self.collateral_mint.decimals)?;
+
+        let event = settle_deposit(&mut self.collateral_bank, &mut self.user_account, self.collateral_mint.key(), amount, self.signer.key())?;
+        emit!(event);
+        Ok(())
+    }
+}
@@ -1,16 +1,22 @@
-use std::f32::consts::E;
-
 use anchor_lang::prelude::*;
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
 
-use crate::{error::ErrorCode, Bank, User, MAXIMUM_AGE, SOL_USD_FEED_ID, USDC_USD_FEED_ID};
+use crate::constants::{BPS, LIQUIDATION_DUST_VALUE, LIQUIDATION_TARGET_HEALTH, SETTLE_ALL};
+use crate::error::ErrorCode;
+use crate::events::LiquidateEvent;
+use crate::health::{apply_bps, token_value, value_to_amount, Health, Leg};
+use crate::instructions::deposit::settle_deposit;
+use crate::instructions::repay::{settle_fixed_repay, settle_repay};
+use crate::oracle::get_price;
+use crate::{Bank, User};
 
 #[derive(Accounts)]
 pub struct Liquidate<'info>{
     #[account(mut)]
     pub liquidator: Signer<'info>,
-    pub price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
     pub collateral_mint: InterfaceAccount<'info, Mint>,
     pub borrowed_mint: InterfaceAccount<'info, Mint>,
 
@@ -30,7 +36,7 @@ pub struct Liquidate<'info>{
 
     #[account(
         mut, 
-        seeds = [b"borrowed", borrowed_mint.key().as_ref()],
+        seeds = [b"bank", borrowed_mint.key().as_ref()],
         bump,
     )]  
     pub borrowed_bank: Account<'info, Bank>,
@@ -45,11 +51,24 @@ pub struct Liquidate<'info>{
 
     #[account(
         mut,
-        seeds=[liquidator.key().as_ref()],
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
         bump,
+        constraint = user_account.spans_both_legs(&collateral_mint.key(), &borrowed_mint.key()) @ ErrorCode::InvalidMint,
     )]
     pub user_account: Account<'info, User>,
 
+    /// The liquidator's own obligation. When passed, the seized collateral is credited to it
+    /// as deposit shares instead of being transferred out of the treasury.
+    #[account(
+        mut,
+        seeds=[b"user", liquidator.key().as_ref(), &[liquidator_account.index]],
+        bump,
+        constraint = liquidator_account.key() != user_account.key() @ ErrorCode::Unauthorized,
+        constraint = liquidator_account.accepts_mint(&collateral_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub liquidator_account: Option<Account<'info, User>>,
+
+    /// Receives the seized collateral, required unless `liquidator_account` is passed.
     #[account(
         init_if_needed,
         payer=liquidator,
@@ -57,7 +76,7 @@ pub struct Liquidate<'info>{
         associated_token::authority=liquidator,
         associated_token::token_program=token_program,
     )]
-    pub liquidator_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub liquidator_collateral_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         init_if_needed,
@@ -75,41 +94,104 @@ pub struct Liquidate<'info>{
 
 
 impl <'info>Liquidate<'info> {
-    pub fn process_liquidate(&mut self, bumps: &LiquidateBumps) -> Result<()>{
-        let collateral_bank = &mut self.collateral_bank;
-        let user = &mut self.user_account;
-        let borrowed_bank = &mut self.borrowed_bank;
+    /// Repays up to the close factor of the obligation's debt in `borrowed_mint` and seizes
+    /// the equivalent collateral plus the liquidation bonus. `SETTLE_ALL` repays the maximum allowed.
+    pub fn process_liquidate(&mut self, repay_amount: u64, bumps: &LiquidateBumps) -> Result<()>{
+        self.liquidate(DebtKind::Variable, repay_amount, bumps)
+    }
+
+    /// Same as `process_liquidate` for the fixed-term loan in `borrowed_mint`. Once the loan is
+    /// past maturity plus the bank's grace period it can be repaid in full whatever the health.
+    pub fn process_liquidate_fixed(&mut self, repay_amount: u64, bumps: &LiquidateBumps) -> Result<()>{
+        self.liquidate(DebtKind::Fixed, repay_amount, bumps)
+    }
 
-        let price_update = &mut self.price_update;
+    fn liquidate(&mut self, kind: DebtKind, repay_amount: u64, bumps: &LiquidateBumps) -> Result<()>{
+        let collateral_key = self.collateral_mint.key();
+        let borrowed_key = self.borrowed_mint.key();
+        let collateral_decimals = self.collateral_mint.decimals;
+        let borrowed_decimals = self.borrowed_mint.decimals;
 
-        let sol_feed_id = get_feed_id_from_hex(SOL_USD_FEED_ID)?;
-        let usdc_feed_id = get_feed_id_from_hex(USDC_USD_FEED_ID)?;
+        let collateral_price = get_price(&self.collateral_price_update, &self.collateral_bank.oracle_feed_id)?;
+        let borrowed_price = get_price(&self.borrowed_price_update, &self.borrowed_bank.oracle_feed_id)?;
 
-        let sol_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &sol_feed_id)?;
-        let usdc_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &usdc_feed_id)?;
-        
-        let total_collateral: u64;
-        let total_borrowed: u64;
+        self.user_account.require_fresh()?;
+        let now = Clock::get()?.unix_timestamp;
+        self.collateral_bank.accrue_interest(now)?;
+        self.borrowed_bank.accrue_interest(now)?;
+        let overdue = kind == DebtKind::Fixed
+            && self.user_account.fixed_loan(&borrowed_key).is_overdue(now, self.borrowed_bank.fixed_grace_period);
+        let mut terms = LiquidationTerms::compute(
+            Leg::new(&self.collateral_bank, collateral_price, collateral_decimals, &self.user_account),
+            Leg::new(&self.borrowed_bank, borrowed_price, borrowed_decimals, &self.user_account),
+            kind,
+            self.user_account.liquidatable_since,
+            now,
+        )?;
 
-        match self.collateral_mint.key() {
-            key if key == user.usdc_address =>{
-                let new_usdc = calculate_accrued_interest(user.deposit_usdc, collateral_bank.interest_rate, user.last_updated)?;
-                total_collateral= usdc_price.price as u64 * new_usdc;
-                let new_sol = calculate_accrued_interest(user.deposit_sol, borrowed_bank.interest_rate, user.last_updated_borrowed)?;
-                total_borrowed= sol_price.price as u64 * new_sol;   
+        let health_before = terms.health;
+        if !health_before.is_liquidatable() && !overdue {
+            return Err(ErrorCode::NotUndercollateralized.into());
+        }
+        if overdue {
+            terms.max_repay = terms.debt;
+        }
+
+        let repay_amount = if repay_amount == SETTLE_ALL {
+            terms.max_repay
+        } else {
+            require!(repay_amount <= terms.max_repay, ErrorCode::LiquidationTooLarge);
+            repay_amount
+        };
+
+        let debt = terms.debt;
+        let bonus_rate = terms.bonus_rate;
+        let must_improve_health = terms.must_improve_health();
+        let Seizure { repay_amount, collateral_seized, bonus } = terms.seize(repay_amount)?;
+        require!(collateral_seized > 0, ErrorCode::ZeroShares);
+
+        // The protocol's cut of the bonus stays in the treasury as protocol fees.
+        let protocol_fee = u64::try_from(apply_bps(bonus as u128, self.collateral_bank.liquity_protocol_fee)?)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        let liquidator_collateral = collateral_seized - protocol_fee;
+
+        let debt_left = match kind {
+            DebtKind::Variable => {
+                let repaid = settle_repay(&mut self.borrowed_bank, &mut self.user_account, borrowed_key, repay_amount, self.liquidator.key())?;
+                self.borrowed_bank.borrow_shares_to_amount(repaid.user_borrow_share)?
             }
-            _=> {
-                let new_sol = calculate_accrued_interest(user.deposit_sol, collateral_bank.interest_rate, user.last_updated)?;
-                total_collateral= sol_price.price as u64 * new_sol;
-                let new_usdc = calculate_accrued_interest(user.deposit_usdc, borrowed_bank.interest_rate, user.last_updated_borrowed)?;
-                total_borrowed= usdc_price.price as u64 * new_usdc;
+            DebtKind::Fixed => {
+                settle_fixed_repay(&mut self.borrowed_bank, &mut self.user_account, borrowed_key, repay_amount, self.liquidator.key())?.remaining
             }
-        }
+        };
+        seize_collateral(&mut self.collateral_bank, &mut self.user_account, collateral_key, collateral_seized)?;
+        let collateral_bank = &mut self.collateral_bank;
+        collateral_bank.protocol_fees = collateral_bank.protocol_fees.checked_add(protocol_fee).ok_or(ErrorCode::MathOverflow)?;
 
-        let health_factor = ((total_collateral as f64 * collateral_bank.liquity_threshold as f64)/total_borrowed as f64) as f64;
+        let health_after = self.health(collateral_price, borrowed_price)?;
+        let collateral_left = self.user_account.deposit_share(&collateral_key);
+        // Once the collateral is gone, whatever debt remains is left for `handle_bad_debt`.
+        if collateral_left > 0 {
+            self.borrowed_bank.check_min_debt_amount(debt_left)?;
+        }
+        // Below break-even no repayment can raise health, so the check only applies above it.
+        // Overdue loans are collected whatever the health.
+        if !overdue && must_improve_health && collateral_left > 0
+            && health_after.health_factor() <= health_before.health_factor()
+        {
+            return Err(ErrorCode::LiquidationNotHealthier.into());
+        }
+        // Only closing out the whole debt may take the obligation past the target health.
+        if !overdue && repay_amount < debt && health_after.health_factor() > LIQUIDATION_TARGET_HEALTH {
+            return Err(ErrorCode::OverLiquidated.into());
+        }
 
-        if health_factor >= 1.0 {
-            return Err(ErrorCode::NotUnderCollecteralized.into());
+        // Keep the auction clock running across partial liquidations.
+        let user = &mut self.user_account;
+        if !health_after.is_liquidatable() {
+            user.liquidatable_since = 0;
+        } else if user.liquidatable_since == 0 {
+            user.liquidatable_since = now;
         }
 
         let transfer_to_bank= TransferChecked{
@@ -121,39 +203,283 @@ impl <'info>Liquidate<'info> {
 
         let cpi_program = self.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program.clone(), transfer_to_bank);
-        let decimals = self.borrowed_mint.decimals;
+        transfer_checked(cpi_ctx, repay_amount, borrowed_decimals)?;
+
+        // Crediting shares leaves the tokens in the treasury, so it works even when all idle collateral is lent out.
+        let shares_to_liquidator = match self.liquidator_account.as_mut() {
+            Some(liquidator_account) => {
+                let credited = settle_deposit(&mut self.collateral_bank, liquidator_account, collateral_key, liquidator_collateral, self.liquidator.key())?;
+                credited.shares_minted
+            }
+            None => {
+                let liquidator_collateral_token_account = self.liquidator_collateral_token_account.as_ref()
+                    .ok_or(ErrorCode::MissingTokenAccount)?;
+                let transfer_to_liquidator = TransferChecked {
+                    from: self.collateral_bank_token_account.to_account_info(),
+                    to: liquidator_collateral_token_account.to_account_info(),
+                    mint: self.collateral_mint.to_account_info(),
+                    authority: self.collateral_bank_token_account.to_account_info()
+                };
 
-        let liquidation_amount = total_borrowed.checked_mul(borrowed_bank.liquity_close_factor).unwrap();
-        transfer_checked(cpi_ctx, liquidation_amount, decimals);
+                let signer_seeds: &[&[&[u8]]] =&[
+                    &[
+                        b"treasury",
+                        collateral_key.as_ref(),
+                        &[bumps.collateral_bank_token_account]
+                    ]
+                ];
 
-        
-        let transfer_to_liquidator = TransferChecked {
-            from: self.collateral_bank_token_account.to_account_info(),
-            to: self.liquidator_collateral_token_account.to_account_info(),
-            mint: self.collateral_mint.to_account_info(),
-            authority: self.collateral_bank_token_account.to_account_info()
+                let cpi_ctx_to_liquidator = CpiContext::new(cpi_program, transfer_to_liquidator).with_signer(signer_seeds);
+                transfer_checked(cpi_ctx_to_liquidator, liquidator_collateral, collateral_decimals)?;
+                0
+            }
         };
-        let liquidator_amount = (liquidation_amount * collateral_bank.liquity_bonus) + liquidation_amount;
-        
-        let mint_key = self.collateral_mint.key();
-        let signer_seeds: &[&[&[u8]]] =&[ 
-                &[
-                    b"treasury",
-                    mint_key.as_ref(),
-                    &[bumps.collateral_bank_token_account]
-                ]
-            ];
-            
-            let cpi_ctx_to_liquidator = CpiContext::new(cpi_program.clone(), transfer_to_liquidator).with_signer(signer_seeds);
-            let collateral_decimal = self.collateral_mint.decimals;
-            transfer_checked(cpi_ctx_to_liquidator, liquidator_amount, collateral_decimal)?;
+
+        emit!(LiquidateEvent {
+            liquidator: self.liquidator.key(),
+            user: self.user_account.key(),
+            collateral_bank: self.collateral_bank.key(),
+            collateral_mint: collateral_key,
+            borrowed_bank: self.borrowed_bank.key(),
+            borrowed_mint: borrowed_key,
+            debt_repaid: repay_amount,
+            collateral_seized,
+            bonus,
+            bonus_bps: bonus_rate,
+            protocol_fee,
+            liquidator_collateral,
+            shares_to_liquidator,
+            collateral_price: collateral_price.price,
+            collateral_price_exponent: collateral_price.exponent,
+            borrowed_price: borrowed_price.price,
+            borrowed_price_exponent: borrowed_price.exponent,
+            health_before: health_before.health_factor(),
+            health_after: health_after.health_factor(),
+            fixed_term: kind == DebtKind::Fixed,
+        });
         Ok(())
     }
+
+    fn health(&self, collateral_price: Price, borrowed_price: Price) -> Result<Health> {
+        Health::compute(&[
+            Leg::new(&self.collateral_bank, collateral_price, self.collateral_mint.decimals, &self.user_account),
+            Leg::new(&self.borrowed_bank, borrowed_price, self.borrowed_mint.decimals, &self.user_account),
+        ])
+    }
 }
 
-fn calculate_accrued_interest(deposited: u64, interest_rate: u64, last_update: i64) -> Result<u64>{
-    let current_time = Clock::get()?.unix_timestamp;
-    let time_elapsed = current_time - last_update;
-    let new_value = (deposited as f64 * E.powf(interest_rate as f32 * time_elapsed as f32) as f64) as u64;
-    Ok(new_value)
+/// Which of the obligation's debts in the borrowed bank a liquidation repays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebtKind {
+    Variable,
+    Fixed,
 }
+
+/// Liquidation parameters for an obligation at current prices, shared by `liquidate` and `preview_liquidation`.
+pub struct LiquidationTerms<'a> {
+    pub collateral: Leg<'a>,
+    pub borrowed: Leg<'a>,
+    pub health: Health,
+    /// Current debt in the borrowed bank, interest included.
+    pub debt: u64,
+    /// Largest repayment allowed: the close factor's share of the debt, cut down to what brings
+    /// the obligation back to `LIQUIDATION_TARGET_HEALTH`, or the whole debt when it is dust.
+    pub max_repay: u64,
+    /// Bonus paid on top of the repaid value, in bps.
+    pub bonus_rate: u64,
+    /// Health factor, in bps, below which seizing collateral at `bonus_rate` lowers health
+    /// faster than repaying raises it.
+    pub break_even: u64,
+    /// Collateral the obligation still holds in the collateral bank.
+    pub available_collateral: u64,
+}
+
+pub struct Seizure {
+    pub repay_amount: u64,
+    pub collateral_seized: u64,
+    pub bonus: u64,
+}
+
+impl<'a> LiquidationTerms<'a> {
+    pub fn compute(collateral: Leg<'a>, borrowed: Leg<'a>, kind: DebtKind, liquidatable_since: i64, now: i64) -> Result<Self> {
+        let health = Health::compute(&[collateral, borrowed])?;
+
+        // Small debts, and ones a partial liquidation would leave below `min_debt`, are closed in full.
+        let debt = match kind {
+            DebtKind::Variable => borrowed.bank.borrow_shares_to_amount(borrowed.borrow_share)?,
+            DebtKind::Fixed => borrowed.fixed_debt,
+        };
+        let min_debt = borrowed.bank.min_debt;
+        let seconds_liquidatable = match liquidatable_since {
+            0 => 0,
+            since => now.saturating_sub(since),
+        };
+        let bonus_rate = collateral.bank.liquidation_bonus(health.health_factor(), seconds_liquidatable);
+        let break_even = collateral.bank.liquidation_break_even(bonus_rate);
+        let available_collateral = collateral.bank.deposit_shares_to_amount(collateral.deposit_share)?;
+
+        let debt_value = token_value(debt, &borrowed.price, borrowed.decimals, true)?;
+        let close_factor_repay = u64::try_from(apply_bps(debt as u128, borrowed.bank.liquity_close_factor)?).map_err(|_| ErrorCode::MathOverflow)?;
+        let partial_repay = close_factor_repay.min(target_repay(&health, &borrowed, break_even)?);
+        let max_repay = if debt_value <= LIQUIDATION_DUST_VALUE || debt - partial_repay < min_debt {
+            debt
+        } else {
+            partial_repay
+        };
+
+        Ok(LiquidationTerms { collateral, borrowed, health, debt, max_repay, bonus_rate, break_even, available_collateral })
+    }
+
+    /// Whether a liquidation has to leave the obligation healthier than it found it.
+    pub fn must_improve_health(&self) -> bool {
+        self.health.health_factor() > self.break_even
+    }
+
+    /// Collateral seized for repaying `repay_amount`, bonus included. When the obligation
+    /// can't cover the bonus, all of its collateral is seized and the repayment scaled down.
+    pub fn seize(&self, repay_amount: u64) -> Result<Seizure> {
+        let (collateral, borrowed) = (&self.collateral, &self.borrowed);
+        let bonus_bps = BPS.checked_add(self.bonus_rate).ok_or(ErrorCode::MathOverflow)?;
+
+        let mut repay_amount = repay_amount;
+        let repaid_value = token_value(repay_amount, &borrowed.price, borrowed.decimals, false)?;
+        let mut collateral_seized = value_to_amount(apply_bps(repaid_value, bonus_bps)?, &collateral.price, collateral.decimals, false)?;
+
+        if collateral_seized > self.available_collateral {
+            collateral_seized = self.available_collateral;
+            let available_value = token_value(collateral_seized, &collateral.price, collateral.decimals, false)?;
+            let covered_value = available_value
+                .checked_mul(BPS as u128)
+                .and_then(|v| v.checked_div(bonus_bps as u128))
+                .ok_or(ErrorCode::MathOverflow)?;
+            repay_amount = value_to_amount(covered_value, &borrowed.price, borrowed.decimals, true)?.min(self.debt);
+        }
+
+        let repaid_value = token_value(repay_amount, &borrowed.price, borrowed.decimals, false)?;
+        let collateral_base = value_to_amount(repaid_value, &collateral.price, collateral.decimals, false)?;
+        let bonus = collateral_seized.saturating_sub(collateral_base);
+
+        Ok(Seizure { repay_amount, collateral_seized, bonus })
+    }
+}
+
+/// Repayment that brings `health` up to `LIQUIDATION_TARGET_HEALTH` when collateral is seized
+/// at `break_even`, solving `(L - r * break_even) / (D - r) = target` for the repaid value `r`.
+/// Unbounded when seizing can't lift health to the target.
+fn target_repay(health: &Health, borrowed: &Leg, break_even: u64) -> Result<u64> {
+    let target = LIQUIDATION_TARGET_HEALTH;
+    if target <= break_even || health.health_factor() >= target {
+        return Ok(u64::MAX);
+    }
+    let repay_value = health.debt_value
+        .checked_mul(target as u128)
+        .and_then(|v| v.checked_sub(health.liquidation_value.checked_mul(BPS as u128)?))
+        .and_then(|v| v.checked_div((target - break_even) as u128))
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(value_to_amount(repay_value, &borrowed.price, borrowed.decimals, false).unwrap_or(u64::MAX))
+}
+
+/// Burns the deposit shares backing `amount` of collateral, rounding the share count up.
+fn seize_collateral(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, amount: u64) -> Result<()> {
+    user.settle_rewards(bank, Clock::get()?.unix_timestamp)?;
+
+    let held_shares = user.deposit_share(&mint);
+    let shares = bank.shares_for_withdraw(amount)?.min(held_shares);
+
+    match mint {
+        key if key == user.usdc_address => {
+            user.deposit_usdc_share = user.deposit_usdc_share.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+            user.deposit_usdc = if user.deposit_usdc_share == 0 { 0 } else { user.deposit_usdc.saturating_sub(amount) };
+        }
+        _ => {
+            user.deposit_sol_share = user.deposit_sol_share.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+            user.deposit_sol = if user.deposit_sol_share == 0 { 0 } else { user.deposit_sol.saturating_sub(amount) };
+        }
+    }
+
+    bank.total_deposit = bank.total_deposit.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_share = bank.total_deposit_share.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::DEFAULT_MAX_LIQUIDATION_BONUS;
+    use crate::test_utils::{bank, price, user};
+
+    const USDC: u64 = 1_000_000;
+    const SOL: u64 = 1_000_000_000;
+
+    /// 1,000 USDC of collateral against `debt` lamports of SOL at $100.
+    fn scenario(debt: u64) -> (Bank, Bank, User) {
+        let (usdc_mint, sol_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut user = user(usdc_mint, sol_mint);
+        user.deposit_usdc_share = 1_000 * USDC;
+        user.borrow_sol_share = debt;
+        (bank(usdc_mint, 8_000, 7_500), bank(sol_mint, 8_000, 7_500), user)
+    }
+
+    fn terms<'a>(usdc_bank: &'a Bank, sol_bank: &'a Bank, user: &User) -> LiquidationTerms<'a> {
+        LiquidationTerms::compute(
+            Leg::new(usdc_bank, price(100_000_000, -8), 6, user),
+            Leg::new(sol_bank, price(10_000_000_000, -8), 9, user),
+            DebtKind::Variable,
+            0,
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn max_repay_stops_at_target_health() {
+        // $808 of debt against $800 of liquidation value, health 0.99.
+        let (usdc_bank, sol_bank, mut user) = scenario(8_080_000_000);
+        let terms = terms(&usdc_bank, &sol_bank, &user);
+        assert_eq!(terms.health.health_factor(), 9_900);
+        assert!(terms.must_improve_health());
+
+        let close_factor_repay = 4_040_000_000;
+        assert!(terms.max_repay < close_factor_repay);
+
+        let seizure = terms.seize(terms.max_repay).unwrap();
+        user.borrow_sol_share -= seizure.repay_amount;
+        user.deposit_usdc_share -= seizure.collateral_seized;
+        let after = Health::compute(&[
+            Leg::new(&usdc_bank, price(100_000_000, -8), 6, &user),
+            Leg::new(&sol_bank, price(10_000_000_000, -8), 9, &user),
+        ])
+        .unwrap();
+        assert!((10_990..=LIQUIDATION_TARGET_HEALTH).contains(&after.health_factor()));
+    }
+
+    #[test]
+    fn deep_shortfall_stays_liquidatable() {
+        // $1,143 of debt against $800 of liquidation value, health 0.7, past break-even.
+        let (usdc_bank, sol_bank, user) = scenario(11_430_000_000);
+        let terms = terms(&usdc_bank, &sol_bank, &user);
+        assert_eq!(terms.health.health_factor(), 6_999);
+        assert_eq!(terms.bonus_rate, DEFAULT_MAX_LIQUIDATION_BONUS);
+        assert_eq!(terms.break_even, 8_800);
+        assert!(!terms.must_improve_health());
+        assert_eq!(terms.max_repay, 5_715_000_000);
+
+        let seizure = terms.seize(terms.max_repay).unwrap();
+        assert_eq!(seizure.repay_amount, 5_715_000_000);
+        assert_eq!(seizure.collateral_seized, 628_650_000);
+        assert_eq!(seizure.bonus, 57_150_000);
+    }
+
+    #[test]
+    fn seize_scales_repayment_to_available_collateral() {
+        // $1,500 of debt against $1,000 of collateral: the close factor's $750 plus a 10%
+        // bonus fits, the whole debt doesn't.
+        let (usdc_bank, sol_bank, user) = scenario(15 * SOL);
+        let terms = terms(&usdc_bank, &sol_bank, &user);
+        let seizure = terms.seize(terms.debt).unwrap();
+        assert_eq!(seizure.collateral_seized, 1_000 * USDC);
+        assert_eq!(seizure.repay_amount, 9_090_909_090);
+        assert!(seizure.bonus > 0);
+    }
+}
+
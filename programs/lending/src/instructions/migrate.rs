@@ -0,0 +1,300 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_lang::Discriminator;
+
+use crate::constants::{ACCOUNT_VERSION, BANK_RESERVED, DEFAULT_MAX_LIQUIDATION_BONUS, MAX_REWARDS, USER_RESERVED};
+use crate::error::ErrorCode;
+use crate::{Bank, FixedLoan, RewardInfo, User, UserReward};
+
+/// v1 valued every non-USDC leg with the SOL/USD feed
+/// (0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d).
+const V1_SOL_USD_FEED_ID: [u8; 32] = [
+    239, 13, 139, 111, 218, 44, 235, 164, 29, 161, 93, 64, 149, 209, 218, 57,
+    42, 13, 47, 142, 208, 198, 199, 188, 15, 76, 250, 200, 194, 128, 181, 109,
+];
+
+/// `Bank` as laid out before versioning.
+#[derive(AnchorDeserialize, InitSpace)]
+pub(crate) struct BankV1 {
+    pub authority: Pubkey,
+    pub mint_address: Pubkey,
+    pub total_deposit: u64,
+    pub total_deposit_share: u64,
+    pub total_borrow: u64,
+    pub total_borrow_share: u64,
+    pub liquity_threshold: u64,
+    pub liquity_bonus: u64,
+    pub liquity_close_factor: u64,
+    pub max_ltv: u64,
+    pub last_updated: u64,
+    pub interest_rate: u64,
+}
+
+/// `User` as laid out before versioning, seeded by `[b"user", owner]` alone.
+#[derive(AnchorDeserialize, InitSpace)]
+pub(crate) struct UserV1 {
+    pub owner: Pubkey,
+    pub deposit_sol: u64,
+    pub deposit_sol_share: u64,
+    pub borrow_sol: u64,
+    pub borrow_sol_share: u64,
+    pub deposit_usdc: u64,
+    pub deposit_usdc_share: u64,
+    pub borrow_usdc: u64,
+    pub borrow_usdc_share: u64,
+    pub usdc_address: Pubkey,
+    pub last_updated: i64,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// Pays the rent for the migrated account. Anyone may migrate a bank, only the owner an obligation.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: a v1 `Bank` or `User`, recognised by its discriminator and v1 length.
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+
+    /// v1 obligations had no index in their seeds, so a v1 `User` moves to its owner's
+    /// obligation 0, which must not exist yet. Unused for banks.
+    #[account(
+        init,
+        payer=payer,
+        space=8 + User::INIT_SPACE,
+        seeds=[b"user", payer.key().as_ref(), &[0]],
+        bump,
+    )]
+    pub user_account: Option<Account<'info, User>>,
+
+    /// Banks of a v1 obligation's legs, already migrated. Required for every leg holding shares,
+    /// its reward progress starts at the bank's current index.
+    #[account(mut)]
+    pub usdc_bank: Option<Account<'info, Bank>>,
+    #[account(mut)]
+    pub sol_bank: Option<Account<'info, Bank>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>MigrateAccount<'info> {
+    /// Upgrades a v1 `Bank` in place, or moves a v1 `User` into a new v2 obligation and closes
+    /// the old account. Fields v1 did not have start at their defaults. A migrated bank has no
+    /// oracle feed until its authority sets one, so nothing can be priced against it before then.
+    pub fn process_migrate_account(&mut self) -> Result<()> {
+        let info = self.account.to_account_info();
+        let (discriminator, len) = {
+            let data = info.try_borrow_data()?;
+            let mut discriminator = [0u8; 8];
+            discriminator.copy_from_slice(data.get(..8).ok_or(ErrorCode::NotMigratable)?);
+            (discriminator, data.len())
+        };
+
+        match discriminator {
+            Bank::DISCRIMINATOR if len == 8 + BankV1::INIT_SPACE => self.migrate_bank(info),
+            User::DISCRIMINATOR if len == 8 + UserV1::INIT_SPACE => self.migrate_user(info),
+            _ => Err(ErrorCode::NotMigratable.into()),
+        }
+    }
+
+    fn migrate_bank(&mut self, info: AccountInfo<'info>) -> Result<()> {
+        require!(self.user_account.is_none(), ErrorCode::NotMigratable);
+        let v1 = BankV1::deserialize(&mut &info.try_borrow_data()?[8..])?;
+        let bank = upgrade_bank(v1);
+
+        let new_len = 8 + Bank::INIT_SPACE;
+        let rent = Rent::get()?.minimum_balance(new_len).saturating_sub(info.lamports());
+        if rent > 0 {
+            let cpi_accounts = Transfer {
+                from: self.payer.to_account_info(),
+                to: info.clone(),
+            };
+            transfer(CpiContext::new(self.system_program.to_account_info(), cpi_accounts), rent)?;
+        }
+        info.realloc(new_len, true)?;
+
+        bank.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+
+    fn migrate_user(&mut self, info: AccountInfo<'info>) -> Result<()> {
+        let v1 = UserV1::deserialize(&mut &info.try_borrow_data()?[8..])?;
+        require_keys_eq!(v1.owner, self.payer.key(), ErrorCode::Unauthorized);
+        let now = Clock::get()?.unix_timestamp;
+
+        let usdc_rewards = match self.usdc_bank.as_deref_mut() {
+            Some(bank) => {
+                require_keys_eq!(bank.mint_address, v1.usdc_address, ErrorCode::InvalidMint);
+                starting_rewards(bank, now)?
+            }
+            None => {
+                require!(v1.deposit_usdc_share == 0 && v1.borrow_usdc_share == 0, ErrorCode::InvalidMint);
+                Default::default()
+            }
+        };
+        // v1 never recorded which mint its other leg held, only that it was priced as SOL.
+        let (sol_address, sol_rewards) = match self.sol_bank.as_deref_mut() {
+            Some(bank) => {
+                require!(bank.mint_address != v1.usdc_address && bank.oracle_feed_id == V1_SOL_USD_FEED_ID, ErrorCode::InvalidMint);
+                (bank.mint_address, starting_rewards(bank, now)?)
+            }
+            None => {
+                require!(v1.deposit_sol_share == 0 && v1.borrow_sol_share == 0, ErrorCode::InvalidMint);
+                (Pubkey::default(), Default::default())
+            }
+        };
+
+        let user_account = self.user_account.as_mut().ok_or(ErrorCode::NotMigratable)?;
+        user_account.set_inner(upgrade_user(v1, sol_address, usdc_rewards, sol_rewards));
+
+        let payer = self.payer.to_account_info();
+        **payer.try_borrow_mut_lamports()? = payer.lamports().checked_add(info.lamports()).ok_or(ErrorCode::MathOverflow)?;
+        **info.try_borrow_mut_lamports()? = 0;
+        info.assign(&System::id());
+        info.realloc(0, false)?;
+        Ok(())
+    }
+}
+
+/// Reward progress for a leg that held shares before rewards existed, so it earns nothing for that time.
+fn starting_rewards(bank: &mut Bank, now: i64) -> Result<[UserReward; MAX_REWARDS]> {
+    bank.accrue_rewards(now)?;
+    Ok(bank.rewards.map(|reward| UserReward { index: reward.index, accrued: 0 }))
+}
+
+pub(crate) fn upgrade_bank(v1: BankV1) -> Bank {
+    Bank {
+        authority: v1.authority,
+        mint_address: v1.mint_address,
+        total_deposit: v1.total_deposit,
+        total_deposit_share: v1.total_deposit_share,
+        total_borrow: v1.total_borrow,
+        total_borrow_share: v1.total_borrow_share,
+        liquity_threshold: v1.liquity_threshold,
+        liquity_bonus: v1.liquity_bonus,
+        liquity_close_factor: v1.liquity_close_factor,
+        max_ltv: v1.max_ltv,
+        liquity_max_bonus: DEFAULT_MAX_LIQUIDATION_BONUS.max(v1.liquity_bonus),
+        liquity_bonus_ramp: 0,
+        liquity_protocol_fee: 0,
+        last_updated: v1.last_updated,
+        interest_rate: v1.interest_rate,
+        paused: false,
+        deposit_cap: 0,
+        borrow_cap: 0,
+        protocol_fees: 0,
+        min_borrow: 0,
+        min_debt: 0,
+        rewards: [RewardInfo::default(); MAX_REWARDS],
+        borrow_fee: 0,
+        referrer_fee_share: 0,
+        total_fixed_borrow: 0,
+        fixed_rate_premium: 0,
+        max_fixed_term: 0,
+        fixed_grace_period: 0,
+        oracle_feed_id: [0; 32],
+        version: ACCOUNT_VERSION,
+        reserved: [0; BANK_RESERVED],
+    }
+}
+
+pub(crate) fn upgrade_user(v1: UserV1, sol_address: Pubkey, usdc_rewards: [UserReward; MAX_REWARDS], sol_rewards: [UserReward; MAX_REWARDS]) -> User {
+    User {
+        owner: v1.owner,
+        index: 0,
+        deposit_sol: v1.deposit_sol,
+        deposit_sol_share: v1.deposit_sol_share,
+        borrow_sol: v1.borrow_sol,
+        borrow_sol_share: v1.borrow_sol_share,
+        deposit_usdc: v1.deposit_usdc,
+        deposit_usdc_share: v1.deposit_usdc_share,
+        borrow_usdc: v1.borrow_usdc,
+        borrow_usdc_share: v1.borrow_usdc_share,
+        usdc_address: v1.usdc_address,
+        sol_address,
+        last_updated: v1.last_updated,
+        last_updated_borrowed: v1.last_updated,
+        delegate: Pubkey::default(),
+        delegate_can_borrow: false,
+        delegate_can_withdraw: false,
+        liquidatable_since: 0,
+        last_refresh_slot: 0,
+        collateral_value: 0,
+        liquidation_value: 0,
+        borrow_limit: 0,
+        debt_value: 0,
+        usdc_rewards,
+        sol_rewards,
+        fixed_usdc: FixedLoan::default(),
+        fixed_sol: FixedLoan::default(),
+        in_batch: false,
+        version: ACCOUNT_VERSION,
+        reserved: [0; USER_RESERVED],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u64s(data: &mut Vec<u8>, values: &[u64]) {
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn v1_layouts_match_the_baseline() {
+        assert_eq!(BankV1::INIT_SPACE, 2 * 32 + 10 * 8);
+        assert_eq!(UserV1::INIT_SPACE, 32 + 8 * 8 + 32 + 8);
+    }
+
+    #[test]
+    fn migrates_a_baseline_bank() {
+        let (authority, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = Bank::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        u64s(&mut data, &[1_000, 900, 400, 380, 8_000, 500, 5_000, 7_500, 1_700_000_000, 0]);
+        assert_eq!(data.len(), 8 + BankV1::INIT_SPACE);
+
+        let bank = upgrade_bank(BankV1::deserialize(&mut &data[8..]).unwrap());
+        let mut migrated = vec![0u8; 8 + Bank::INIT_SPACE];
+        bank.try_serialize(&mut &mut migrated[..]).unwrap();
+        let bank = Bank::try_deserialize(&mut &migrated[..]).unwrap();
+
+        assert_eq!((bank.authority, bank.mint_address), (authority, mint));
+        assert_eq!((bank.total_deposit, bank.total_deposit_share), (1_000, 900));
+        assert_eq!((bank.total_borrow, bank.total_borrow_share), (400, 380));
+        assert_eq!((bank.liquity_threshold, bank.liquity_bonus, bank.liquity_close_factor, bank.max_ltv), (8_000, 500, 5_000, 7_500));
+        assert_eq!(bank.last_updated, 1_700_000_000);
+        assert_eq!(bank.liquity_max_bonus, DEFAULT_MAX_LIQUIDATION_BONUS);
+        assert_eq!(bank.oracle_feed_id, [0; 32]);
+        assert_eq!(bank.version, ACCOUNT_VERSION);
+    }
+
+    #[test]
+    fn migrates_a_baseline_user() {
+        let (owner, usdc, sol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = User::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(owner.as_ref());
+        u64s(&mut data, &[10, 9, 4, 3, 100, 95, 0, 0]);
+        data.extend_from_slice(usdc.as_ref());
+        u64s(&mut data, &[1_700_000_000]);
+        assert_eq!(data.len(), 8 + UserV1::INIT_SPACE);
+
+        let usdc_rewards = [UserReward { index: 7, accrued: 0 }; MAX_REWARDS];
+        let user = upgrade_user(UserV1::deserialize(&mut &data[8..]).unwrap(), sol, usdc_rewards, Default::default());
+        let mut migrated = vec![0u8; 8 + User::INIT_SPACE];
+        user.try_serialize(&mut &mut migrated[..]).unwrap();
+        let user = User::try_deserialize(&mut &migrated[..]).unwrap();
+
+        assert_eq!((user.owner, user.index, user.usdc_address, user.sol_address), (owner, 0, usdc, sol));
+        assert_eq!((user.deposit_sol, user.deposit_sol_share, user.borrow_sol, user.borrow_sol_share), (10, 9, 4, 3));
+        assert_eq!((user.deposit_usdc, user.deposit_usdc_share, user.borrow_usdc_share), (100, 95, 0));
+        assert_eq!(user.usdc_rewards[0].index, 7);
+        assert_eq!(user.delegate, Pubkey::default());
+        assert!(!user.fixed_sol.is_active() && !user.in_batch);
+        assert_eq!(user.version, ACCOUNT_VERSION);
+    }
+}
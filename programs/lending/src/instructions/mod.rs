@@ -7,4 +7,26 @@ pub use withdraw::*;
 pub mod borrow;
 pub use borrow::*;
 pub mod repay;
-pub use repay::*;
\ No newline at end of file
+pub use repay::*;
+pub mod liquidate;
+pub use liquidate::*;
+pub mod bad_debt;
+pub use bad_debt::*;
+pub mod delegate;
+pub use delegate::*;
+pub mod leverage;
+pub use leverage::*;
+pub mod swap_collateral;
+pub use swap_collateral::*;
+pub mod refinance;
+pub use refinance::*;
+pub mod batch;
+pub use batch::*;
+pub mod migrate;
+pub use migrate::*;
+pub mod rewards;
+pub use rewards::*;
+pub mod refresh;
+pub use refresh::*;
+pub mod views;
+pub use views::*;
@@ -0,0 +1,241 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}
+};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::constants::SETTLE_ALL;
+use crate::error::ErrorCode;
+use crate::events::RefinanceEvent;
+use crate::health::{Health, Leg};
+use crate::instructions::borrow::settle_borrow;
+use crate::instructions::repay::settle_repay;
+use crate::oracle::get_price;
+use crate::swap::swap;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct RefinanceDebt<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// Mint of the debt being paid off.
+    pub source_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", source_mint.key().as_ref()],
+        bump
+    )]
+    pub source_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", source_mint.key().as_ref()],
+        bump
+    )]
+    pub source_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint of the new debt.
+    pub target_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", target_mint.key().as_ref()],
+        bump
+    )]
+    pub target_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", target_mint.key().as_ref()],
+        bump
+    )]
+    pub target_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Only the owner can refinance, the swap runs on their signature and token accounts.
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.owner == signer.key() @ ErrorCode::Unauthorized,
+        constraint = user_account.is_bound_to(&source_mint.key()) @ ErrorCode::InvalidMint,
+        constraint = source_mint.key() != target_mint.key() @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=source_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_source_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=target_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_target_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub source_price_update: Account<'info, PriceUpdateV2>,
+    pub target_price_update: Account<'info, PriceUpdateV2>,
+
+    /// The obligation's other leg, required when the target takes over the source leg since
+    /// health is then computed against it instead of the source bank.
+    pub other_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds=[b"bank", other_bank.mint_address.as_ref()],
+        bump
+    )]
+    pub other_bank: Option<Account<'info, Bank>>,
+    pub other_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// Adapter implementing `swap(amount_in, min_amount_out)`, its accounts follow as remaining accounts.
+    /// CHECK: any program may be used, proceeds are measured from `user_source_token_account`.
+    #[account(executable, constraint = swap_program.key() != crate::ID @ ErrorCode::InvalidConfig)]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>RefinanceDebt<'info> {
+    /// Borrows `borrow_amount` from the target bank, swaps it into the source mint through
+    /// `swap_program` and repays the source bank's variable debt with the proceeds. Proceeds
+    /// beyond what is owed close the source debt and stay with the signer. Health is only
+    /// checked once both legs are settled.
+    ///
+    /// The target is normally the obligation's other leg. Any other mint, e.g. a second
+    /// stablecoin for USDC debt, takes over the source leg instead, so the repayment must leave
+    /// that leg empty with its rewards claimed, and the `other_*` accounts must be passed.
+    pub fn process_refinance_debt(&mut self, borrow_amount: u64, min_amount_out: u64, bumps: &RefinanceDebtBumps, swap_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(!self.target_bank.paused, ErrorCode::BankPaused);
+        self.user_account.require_fresh()?;
+        let now = Clock::get()?.unix_timestamp;
+        self.source_bank.accrue_interest(now)?;
+        self.target_bank.accrue_interest(now)?;
+
+        let source_key = self.source_mint.key();
+        let target_key = self.target_mint.key();
+        let source_price = get_price(&self.source_price_update, &self.source_bank.oracle_feed_id)?;
+        let target_price = get_price(&self.target_price_update, &self.target_bank.oracle_feed_id)?;
+
+        let held_shares = self.user_account.borrow_share(&source_key);
+        require!(held_shares > 0, ErrorCode::ZeroShares);
+        let owed = self.source_bank.borrow_shares_to_amount(held_shares)?;
+
+        let bank = &mut self.target_bank;
+        require!(borrow_amount >= bank.min_borrow, ErrorCode::BelowMinimumBorrow);
+        let fee = bank.origination_fee(borrow_amount)?;
+        let debt = borrow_amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        bank.check_borrow_cap(debt)?;
+
+        // A target the obligation can't hold next to the source only gets its debt recorded
+        // once the source leg is repaid and rebound to it.
+        let takes_over_source = !self.user_account.accepts_mint(&target_key);
+        if !takes_over_source {
+            self.record_borrow(debt, fee, now)?;
+        }
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.target_bank_token_account.to_account_info(),
+            to: self.user_target_token_account.to_account_info(),
+            mint: self.target_mint.to_account_info(),
+            authority: self.target_bank_token_account.to_account_info(),
+        };
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                b"treasury",
+                target_key.as_ref(),
+                &[bumps.target_bank_token_account]
+            ],
+        ];
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+        transfer_checked(cpi_ctx, borrow_amount, self.target_mint.decimals)?;
+
+        let balance_before = self.user_source_token_account.amount;
+        swap(&self.swap_program, swap_accounts, borrow_amount, min_amount_out)?;
+        self.user_source_token_account.reload()?;
+        let swapped_out = self.user_source_token_account.amount.saturating_sub(balance_before);
+        require!(swapped_out >= min_amount_out, ErrorCode::SlippageExceeded);
+
+        let repay_amount = if swapped_out >= owed { SETTLE_ALL } else { swapped_out };
+        let event = settle_repay(&mut self.source_bank, &mut self.user_account, source_key, repay_amount, self.signer.key())?;
+        self.source_bank.check_min_debt(event.user_borrow_share)?;
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.user_source_token_account.to_account_info(),
+            to: self.source_bank_token_account.to_account_info(),
+            authority: self.signer.to_account_info(),
+            mint: self.source_mint.to_account_info()
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts);
+        transfer_checked(cpi_ctx, event.amount, self.source_mint.decimals)?;
+        let repaid = event.amount;
+        emit!(event);
+
+        let health = if takes_over_source {
+            self.user_account.rebind_leg(&source_key, &self.target_bank)?;
+            self.record_borrow(debt, fee, now)?;
+            self.health_with_other_leg(target_price)?
+        } else {
+            Health::compute(&[
+                Leg::new(&self.source_bank, source_price, self.source_mint.decimals, &self.user_account),
+                Leg::new(&self.target_bank, target_price, self.target_mint.decimals, &self.user_account),
+            ])?
+        };
+        if !health.within_borrow_limit() {
+            return Err(ErrorCode::OverBorrowableAmount.into());
+        }
+
+        emit!(RefinanceEvent {
+            signer: self.signer.key(),
+            user: self.user_account.key(),
+            source_bank: self.source_bank.key(),
+            target_bank: self.target_bank.key(),
+            borrowed: borrow_amount,
+            fee,
+            swapped_out,
+            repaid,
+            health_after: health.health_factor(),
+        });
+        Ok(())
+    }
+
+    /// Adds `debt`, origination `fee` included, to the obligation's variable debt in the target bank.
+    fn record_borrow(&mut self, debt: u64, fee: u64, now: i64) -> Result<()> {
+        let bank = &mut self.target_bank;
+        let user = &mut self.user_account;
+        user.bind_mint(bank)?;
+        user.settle_rewards(bank, now)?;
+        bank.protocol_fees = bank.protocol_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        settle_borrow(bank, user, self.target_mint.key(), debt)?;
+        user.last_updated_borrowed = now;
+        Ok(())
+    }
+
+    /// Health over the target leg and the obligation's other leg, once the target has taken over the source leg.
+    fn health_with_other_leg(&self, target_price: Price) -> Result<Health> {
+        let (Some(other_mint), Some(other_bank), Some(other_price_update)) =
+            (self.other_mint.as_ref(), self.other_bank.as_ref(), self.other_price_update.as_ref())
+        else {
+            return Err(ErrorCode::InvalidMint.into());
+        };
+        let user = &self.user_account;
+        require_keys_eq!(other_bank.mint_address, user.other_leg(&self.target_mint.key()), ErrorCode::InvalidMint);
+        require_keys_eq!(other_mint.key(), other_bank.mint_address, ErrorCode::InvalidMint);
+
+        let other_price = get_price(other_price_update, &other_bank.oracle_feed_id)?;
+        Health::compute(&[
+            Leg::new(&self.target_bank, target_price, self.target_mint.decimals, user),
+            Leg::new(other_bank, other_price, other_mint.decimals, user),
+        ])
+    }
+}
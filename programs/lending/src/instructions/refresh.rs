@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::error::ErrorCode;
+use crate::health::{Health, Leg};
+use crate::oracle::get_price;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct RefreshBank<'info>{
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshObligation<'info>{
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    /// Mint of the obligation's other leg.
+    pub other_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", other_mint.key().as_ref()],
+        bump
+    )]
+    pub other_bank: Account<'info, Bank>,
+
+    pub other_price_update: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.spans_both_legs(&mint.key(), &other_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+impl <'info>RefreshBank<'info> {
+    pub fn process_refresh_bank(&mut self) -> Result<()>{
+        self.bank.accrue_interest(Clock::get()?.unix_timestamp)
+    }
+}
+
+impl <'info>RefreshObligation<'info> {
+    /// Accrues both banks and the obligation's fixed-term loans, then caches its values at
+    /// current prices. Anyone can call it, so scanners can keep `liquidatable_since` and
+    /// the cached values current.
+    pub fn process_refresh_obligation(&mut self) -> Result<()>{
+        let clock = Clock::get()?;
+        self.bank.accrue_interest(clock.unix_timestamp)?;
+        self.other_bank.accrue_interest(clock.unix_timestamp)?;
+        self.user_account.accrue_fixed_loans(clock.unix_timestamp)?;
+
+        let price = get_price(&self.price_update, &self.bank.oracle_feed_id)?;
+        let other_price = get_price(&self.other_price_update, &self.other_bank.oracle_feed_id)?;
+
+        let health = Health::compute(&[
+            Leg::new(&self.bank, price, self.mint.decimals, &self.user_account),
+            Leg::new(&self.other_bank, other_price, self.other_mint.decimals, &self.user_account),
+        ])?;
+
+        let user = &mut self.user_account;
+        user.collateral_value = health.collateral_value;
+        user.liquidation_value = health.liquidation_value;
+        user.borrow_limit = health.borrow_limit;
+        user.debt_value = health.debt_value;
+        user.last_refresh_slot = clock.slot;
+
+        if !health.is_liquidatable() {
+            user.liquidatable_since = 0;
+        } else if user.liquidatable_since == 0 {
+            user.liquidatable_since = clock.unix_timestamp;
+        }
+        Ok(())
+    }
+}
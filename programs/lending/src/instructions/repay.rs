@@ -4,8 +4,9 @@ use anchor_spl::{
     token_interface::{TokenAccount, TokenInterface, Mint, TransferChecked, transfer_checked}
 };
 
-use crate::{ Bank, User};
+use crate::{Bank, User, SETTLE_ALL};
 use crate::error::ErrorCode;
+use crate::events::{RepayEvent, RepayFixedEvent};
 
 #[derive(Accounts)]
 pub struct Repay<'info>{
@@ -30,8 +31,10 @@ pub struct Repay<'info>{
 
     #[account(
         mut,
-        seeds= [b"user", signer.key().as_ref()],
-        bump
+        seeds= [b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.is_owner_or_delegate(&signer.key()) @ ErrorCode::Unauthorized,
+        constraint = user_account.accepts_mint(&mint.key()) @ ErrorCode::InvalidMint,
     )]
     pub user_account: Account<'info, User>,
 
@@ -50,24 +53,78 @@ pub struct Repay<'info>{
 }
 
 
+#[derive(Accounts)]
+pub struct RepayFor<'info>{
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds= [b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds= [b"treasury",mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds= [b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.accepts_mint(&mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        mut,
+        associated_token::mint=mint,
+        associated_token::authority=payer,
+        associated_token::token_program=token_program,
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
 impl <'info>Repay<'info> {
     pub fn process_repay(&mut self, amount: u64)-> Result<()>{
-        let user = &mut self.user_account;
+        self.bank.accrue_interest(Clock::get()?.unix_timestamp)?;
+        let event = settle_repay(&mut self.bank, &mut self.user_account, self.mint.key(), amount, self.signer.key())?;
+        self.bank.check_min_debt(event.user_borrow_share)?;
 
-        let borrowed_asset;
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.user_token_account.to_account_info(),
+            to: self.bank_token_account.to_account_info(),
+            authority: self.signer.to_account_info(),
+            mint: self.mint.to_account_info()
+        };
 
-        match self.mint.to_account_info().key() {
-            key if key == user.usdc_address => {
-                borrowed_asset = user.borrow_usdc;
-            }
-            _ => {
-                borrowed_asset = user.borrow_sol;
-            }
-        }
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
+        let decimals = self.mint.decimals;
 
-        if amount > borrowed_asset {
-            return Err(ErrorCode::OverBorrowableAmount.into());
-        }
+        transfer_checked(cpi_ctx, event.amount, decimals)?;
+
+        emit!(event);
+
+        Ok(())
+    }
+}
+
+impl <'info>Repay<'info> {
+    /// Pays down the leg's fixed-term loan, interest first. `SETTLE_ALL` closes it.
+    pub fn process_repay_fixed(&mut self, amount: u64)-> Result<()>{
+        let event = settle_fixed_repay(&mut self.bank, &mut self.user_account, self.mint.key(), amount, self.signer.key())?;
+        self.bank.check_min_debt_amount(event.remaining)?;
 
         let transfer_cpi_accounts = TransferChecked{
             from: self.user_token_account.to_account_info(),
@@ -76,33 +133,131 @@ impl <'info>Repay<'info> {
             mint: self.mint.to_account_info()
         };
 
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
+
+        transfer_checked(cpi_ctx, event.amount, self.mint.decimals)?;
+
+        emit!(event);
+
+        Ok(())
+    }
+}
+
+impl <'info>RepayFor<'info> {
+    /// Anyone can pay down an obligation's debt from their own tokens, the obligation owner does not sign.
+    pub fn process_repay_for(&mut self, amount: u64)-> Result<()>{
+        self.bank.accrue_interest(Clock::get()?.unix_timestamp)?;
+        let event = settle_repay(&mut self.bank, &mut self.user_account, self.mint.key(), amount, self.payer.key())?;
+        self.bank.check_min_debt(event.user_borrow_share)?;
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.payer_token_account.to_account_info(),
+            to: self.bank_token_account.to_account_info(),
+            authority: self.payer.to_account_info(),
+            mint: self.mint.to_account_info()
+        };
+
         let cpi_program = self.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
         let decimals = self.mint.decimals;
 
-        transfer_checked(cpi_ctx, amount, decimals)?;
+        transfer_checked(cpi_ctx, event.amount, decimals)?;
+
+        emit!(event);
+
+        Ok(())
+    }
+}
+
+/// Burns the debt shares covered by `amount` on both the obligation and the bank.
+/// `SETTLE_ALL`, or the exact amount owed, burns every share the obligation holds so
+/// accrued interest is included and no share dust is left behind.
+pub(crate) fn settle_repay(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, amount: u64, payer: Pubkey) -> Result<RepayEvent> {
+    user.settle_rewards(bank, Clock::get()?.unix_timestamp)?;
+
+    let held_shares = match mint {
+        key if key == user.usdc_address => user.borrow_usdc_share,
+        _ => user.borrow_sol_share,
+    };
 
-        let bank = &mut self.bank;
+    let owed = bank.borrow_shares_to_amount(held_shares)?;
+    let (amount, user_shares) = if amount == SETTLE_ALL || amount == owed {
+        (owed, held_shares)
+    } else {
+        if amount > owed {
+            return Err(ErrorCode::OverBorrowableAmount.into());
+        }
+        (amount, bank.shares_for_repay(amount)?)
+    };
+    require!(user_shares > 0, ErrorCode::ZeroShares);
 
-        let borrowed_ratio = amount.checked_div(bank.total_borrow).unwrap();
-        let user_shares = bank.total_borrow_share.checked_mul(borrowed_ratio).unwrap();
+    // Shares left worth less than a token unit are written off so the position can close.
+    let swept_shares = bank.sweep_borrow_dust(held_shares, user_shares)?;
+    let written_off = bank.borrow_shares_to_amount(swept_shares - user_shares)?;
+    let user_shares = swept_shares;
 
-        match self.mint.to_account_info().key() {
-            key if key == user.usdc_address => {
-                user.borrow_usdc -= amount;
-                user.borrow_usdc_share -= user_shares;
+    // Principal is tracked separately from shares, so repaying accrued interest can exceed it.
+    let (user_borrow, user_borrow_share) = match mint {
+        key if key == user.usdc_address => {
+            user.borrow_usdc = user.borrow_usdc.saturating_sub(amount);
+            user.borrow_usdc_share = user.borrow_usdc_share.checked_sub(user_shares).ok_or(ErrorCode::MathOverflow)?;
+            if user.borrow_usdc_share == 0 {
+                user.borrow_usdc = 0;
             }
-            _=>{
-                user.borrow_sol -= amount;
-                user.borrow_sol_share -= user_shares;
+            (user.borrow_usdc, user.borrow_usdc_share)
+        }
+        _=>{
+            user.borrow_sol = user.borrow_sol.saturating_sub(amount);
+            user.borrow_sol_share = user.borrow_sol_share.checked_sub(user_shares).ok_or(ErrorCode::MathOverflow)?;
+            if user.borrow_sol_share == 0 {
+                user.borrow_sol = 0;
             }
+            (user.borrow_sol, user.borrow_sol_share)
         }
+    };
 
-        bank.total_borrow -= amount;
-        bank.total_borrow_share -= user_shares;
+    bank.total_borrow = bank.total_borrow.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?.saturating_sub(written_off);
+    bank.total_borrow_share = bank.total_borrow_share.checked_sub(user_shares).ok_or(ErrorCode::MathOverflow)?;
 
-        Ok(())
+    Ok(RepayEvent {
+        signer: payer,
+        user: user.key(),
+        bank: bank.key(),
+        mint,
+        amount,
+        shares_burned: user_shares,
+        user_borrow,
+        user_borrow_share,
+        bank_total_borrow: bank.total_borrow,
+        bank_total_borrow_share: bank.total_borrow_share,
+    })
+}
 
-    }
+/// Applies `amount` to the obligation's fixed-term loan in `mint`. Repaid interest is
+/// credited to depositors, repaid principal frees up `total_fixed_borrow`.
+pub(crate) fn settle_fixed_repay(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, amount: u64, payer: Pubkey) -> Result<RepayFixedEvent> {
+    let loan = user.fixed_loan_mut(&mint);
+    require!(loan.is_active(), ErrorCode::NoFixedLoan);
+    loan.accrue(Clock::get()?.unix_timestamp)?;
+
+    let amount = if amount == SETTLE_ALL { loan.owed() } else { amount };
+    require!(amount > 0, ErrorCode::ZeroShares);
+    let (interest_paid, principal_paid) = loan.repay(amount)?;
+    let remaining = loan.owed();
 
-}
\ No newline at end of file
+    bank.total_fixed_borrow = bank.total_fixed_borrow.checked_sub(principal_paid).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit = bank.total_deposit.checked_add(interest_paid).ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(RepayFixedEvent {
+        signer: payer,
+        user: user.key(),
+        bank: bank.key(),
+        mint,
+        amount,
+        interest_paid,
+        principal_paid,
+        remaining,
+        bank_total_fixed_borrow: bank.total_fixed_borrow,
+    })
+}
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}
+};
+
+use crate::constants::MAX_REWARDS;
+use crate::error::ErrorCode;
+use crate::events::ClaimRewardsEvent;
+use crate::{Bank, RewardInfo, RewardSide, User};
+
+#[derive(Accounts)]
+pub struct AddReward<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump,
+        has_one = authority,
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    /// Funded by plain token transfers, emissions are only paid while it holds enough.
+    #[account(
+        init,
+        payer=authority,
+        token::mint=reward_mint,
+        token::authority=reward_vault,
+        seeds=[b"reward_vault", bank.key().as_ref(), reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"reward_vault", bank.key().as_ref(), reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.can_withdraw(&signer.key()) @ ErrorCode::Unauthorized,
+        constraint = user_account.is_bound_to(&mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    /// Rewards always go to the obligation owner, even when a delegate signs.
+    #[account(address = user_account.owner)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=reward_mint,
+        associated_token::authority=owner,
+        associated_token::token_program=token_program,
+    )]
+    pub owner_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>AddReward<'info> {
+    /// Starts a reward program in an unused `slot`. Slots are never reused, so rewards
+    /// an obligation has accrued in a slot are always paid in that slot's mint.
+    pub fn process_add_reward(&mut self, slot: u8, side: RewardSide, emission_per_second: u64, start_time: i64, end_time: i64) -> Result<()> {
+        let slot = slot as usize;
+        require!(slot < MAX_REWARDS, ErrorCode::InvalidConfig);
+        require!(start_time < end_time, ErrorCode::InvalidConfig);
+
+        let bank = &mut self.bank;
+        require!(bank.rewards[slot].mint == Pubkey::default(), ErrorCode::InvalidConfig);
+        require!(bank.reward_slot(&self.reward_mint.key()).is_err(), ErrorCode::InvalidConfig);
+
+        bank.rewards[slot] = RewardInfo {
+            mint: self.reward_mint.key(),
+            side,
+            emission_per_second,
+            start_time,
+            end_time,
+            index: 0,
+            last_updated: Clock::get()?.unix_timestamp,
+        };
+        Ok(())
+    }
+}
+
+impl <'info>ClaimRewards<'info> {
+    /// Pays out the obligation's rewards from this bank in `reward_mint`, up to what the vault holds.
+    pub fn process_claim_rewards(&mut self, bumps: &ClaimRewardsBumps) -> Result<()> {
+        let mint = self.mint.key();
+        let slot = self.bank.reward_slot(&self.reward_mint.key())?;
+
+        self.user_account.settle_rewards(&mut self.bank, Clock::get()?.unix_timestamp)?;
+
+        let user_reward = &mut self.user_account.rewards_mut(&mint)[slot];
+        let amount = user_reward.accrued.min(self.reward_vault.amount);
+        user_reward.accrued -= amount;
+        let remaining = user_reward.accrued;
+        require!(amount > 0, ErrorCode::ZeroShares);
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.reward_vault.to_account_info(),
+            to: self.owner_reward_account.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            authority: self.reward_vault.to_account_info(),
+        };
+
+        let bank_key = self.bank.key();
+        let reward_mint_key = self.reward_mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                b"reward_vault",
+                bank_key.as_ref(),
+                reward_mint_key.as_ref(),
+                &[bumps.reward_vault]
+            ],
+        ];
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        emit!(ClaimRewardsEvent {
+            signer: self.signer.key(),
+            user: self.user_account.key(),
+            bank: bank_key,
+            reward_mint: reward_mint_key,
+            amount,
+            remaining,
+        });
+        Ok(())
+    }
+}
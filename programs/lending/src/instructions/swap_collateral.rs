@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}
+};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+use crate::error::ErrorCode;
+use crate::events::SwapCollateralEvent;
+use crate::health::{Health, Leg};
+use crate::instructions::deposit::settle_deposit;
+use crate::instructions::withdraw::settle_withdraw;
+use crate::oracle::get_price;
+use crate::swap::swap;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct SwapCollateral<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub from_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", from_mint.key().as_ref()],
+        bump
+    )]
+    pub from_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", from_mint.key().as_ref()],
+        bump
+    )]
+    pub from_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub to_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds=[b"bank", to_mint.key().as_ref()],
+        bump
+    )]
+    pub to_bank: Account<'info, Bank>,
+
+    #[account(
+        mut,
+        seeds=[b"treasury", to_mint.key().as_ref()],
+        bump
+    )]
+    pub to_bank_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Only the owner can swap collateral, the swap runs on their signature and token accounts.
+    #[account(
+        mut,
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.owner == signer.key() @ ErrorCode::Unauthorized,
+        constraint = user_account.is_bound_to(&from_mint.key()) @ ErrorCode::InvalidMint,
+        constraint = from_mint.key() != to_mint.key() @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=from_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_from_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer=signer,
+        associated_token::mint=to_mint,
+        associated_token::authority=signer,
+        associated_token::token_program=token_program,
+    )]
+    pub user_to_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub from_price_update: Account<'info, PriceUpdateV2>,
+    pub to_price_update: Account<'info, PriceUpdateV2>,
+
+    /// The obligation's other leg, required when the new collateral takes over the `from` leg
+    /// since health is then computed against it instead of the `from` bank.
+    pub other_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds=[b"bank", other_bank.mint_address.as_ref()],
+        bump
+    )]
+    pub other_bank: Option<Account<'info, Bank>>,
+    pub other_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// Adapter implementing `swap(amount_in, min_amount_out)`, its accounts follow as remaining accounts.
+    /// CHECK: any program may be used, proceeds are measured from `user_to_token_account`.
+    #[account(executable, constraint = swap_program.key() != crate::ID @ ErrorCode::InvalidConfig)]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>SwapCollateral<'info> {
+    /// Withdraws `amount` of collateral (`SETTLE_ALL` for all of it), swaps it through
+    /// `swap_program` and deposits the proceeds into the obligation's other leg. Debt is
+    /// left untouched and health is only checked once the new collateral is in place.
+    ///
+    /// Proceeds in a mint the obligation doesn't hold take over the `from` leg instead, so the
+    /// withdrawal must leave that leg empty with its rewards claimed, and the `other_*`
+    /// accounts must be passed.
+    pub fn process_swap_collateral(&mut self, amount: u64, min_amount_out: u64, bumps: &SwapCollateralBumps, swap_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(!self.from_bank.paused && !self.to_bank.paused, ErrorCode::BankPaused);
+        self.user_account.require_fresh()?;
+        let now = Clock::get()?.unix_timestamp;
+        self.from_bank.accrue_interest(now)?;
+        self.to_bank.accrue_interest(now)?;
+
+        let from_key = self.from_mint.key();
+        let to_key = self.to_mint.key();
+        let from_price = get_price(&self.from_price_update, &self.from_bank.oracle_feed_id)?;
+        let to_price = get_price(&self.to_price_update, &self.to_bank.oracle_feed_id)?;
+
+        let takes_over_from = !self.user_account.accepts_mint(&to_key);
+
+        let withdraw_event = settle_withdraw(&mut self.from_bank, &mut self.user_account, from_key, amount, self.signer.key(), &from_price, &to_price)?;
+        let withdrawn = withdraw_event.amount;
+
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.from_bank_token_account.to_account_info(),
+            to: self.user_from_token_account.to_account_info(),
+            mint: self.from_mint.to_account_info(),
+            authority: self.from_bank_token_account.to_account_info(),
+        };
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                b"treasury",
+                from_key.as_ref(),
+                &[bumps.from_bank_token_account]
+            ],
+        ];
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts).with_signer(signer_seeds);
+        transfer_checked(cpi_ctx, withdrawn, self.from_mint.decimals)?;
+        emit!(withdraw_event);
+
+        let balance_before = self.user_to_token_account.amount;
+        swap(&self.swap_program, swap_accounts, withdrawn, min_amount_out)?;
+        self.user_to_token_account.reload()?;
+        let swapped_out = self.user_to_token_account.amount.saturating_sub(balance_before);
+        require!(swapped_out >= min_amount_out, ErrorCode::SlippageExceeded);
+
+        self.to_bank.check_deposit_cap(swapped_out)?;
+        let transfer_cpi_accounts = TransferChecked{
+            from: self.user_to_token_account.to_account_info(),
+            to: self.to_bank_token_account.to_account_info(),
+            authority: self.signer.to_account_info(),
+            mint: self.to_mint.to_account_info()
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_cpi_accounts);
+        transfer_checked(cpi_ctx, swapped_out, self.to_mint.decimals)?;
+        if takes_over_from {
+            self.user_account.rebind_leg(&from_key, &self.to_bank)?;
+        }
+        let deposit_event = settle_deposit(&mut self.to_bank, &mut self.user_account, to_key, swapped_out, self.signer.key())?;
+        emit!(deposit_event);
+
+        let health = if takes_over_from {
+            self.health_with_other_leg(to_price)?
+        } else {
+            Health::compute(&[
+                Leg::new(&self.from_bank, from_price, self.from_mint.decimals, &self.user_account),
+                Leg::new(&self.to_bank, to_price, self.to_mint.decimals, &self.user_account),
+            ])?
+        };
+        if !health.within_borrow_limit() {
+            return Err(ErrorCode::Unhealthy.into());
+        }
+
+        emit!(SwapCollateralEvent {
+            signer: self.signer.key(),
+            user: self.user_account.key(),
+            from_bank: self.from_bank.key(),
+            to_bank: self.to_bank.key(),
+            withdrawn,
+            swapped_out,
+            health_after: health.health_factor(),
+        });
+        Ok(())
+    }
+
+    /// Health over the `to` leg and the obligation's other leg, once `to` has taken over the `from` leg.
+    fn health_with_other_leg(&self, to_price: Price) -> Result<Health> {
+        let (Some(other_mint), Some(other_bank), Some(other_price_update)) =
+            (self.other_mint.as_ref(), self.other_bank.as_ref(), self.other_price_update.as_ref())
+        else {
+            return Err(ErrorCode::InvalidMint.into());
+        };
+        let user = &self.user_account;
+        require_keys_eq!(other_bank.mint_address, user.other_leg(&self.to_mint.key()), ErrorCode::InvalidMint);
+        require_keys_eq!(other_mint.key(), other_bank.mint_address, ErrorCode::InvalidMint);
+
+        let other_price = get_price(other_price_update, &other_bank.oracle_feed_id)?;
+        Health::compute(&[
+            Leg::new(&self.to_bank, to_price, self.to_mint.decimals, user),
+            Leg::new(other_bank, other_price, other_mint.decimals, user),
+        ])
+    }
+}
@@ -0,0 +1,282 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::constants::BPS;
+use crate::error::ErrorCode;
+use crate::health::{value_to_amount, Health, Leg};
+use crate::instructions::liquidate::{DebtKind, LiquidationTerms, Seizure};
+use crate::oracle::get_price;
+use crate::{Bank, User};
+
+#[derive(Accounts)]
+pub struct PreviewLiquidation<'info>{
+    pub collateral_price_update: Account<'info, PriceUpdateV2>,
+    pub borrowed_price_update: Account<'info, PriceUpdateV2>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+    pub borrowed_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds=[b"bank", collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds=[b"bank", borrowed_mint.key().as_ref()],
+        bump
+    )]
+    pub borrowed_bank: Account<'info, Bank>,
+
+    #[account(
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.spans_both_legs(&collateral_mint.key(), &borrowed_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+#[derive(Accounts)]
+pub struct ObligationView<'info>{
+    /// Mint of the bank being queried.
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds=[b"bank", mint.key().as_ref()],
+        bump
+    )]
+    pub bank: Account<'info, Bank>,
+
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    /// Mint of the obligation's other leg.
+    pub other_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds=[b"bank", other_mint.key().as_ref()],
+        bump
+    )]
+    pub other_bank: Account<'info, Bank>,
+
+    pub other_price_update: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
+        bump,
+        constraint = user_account.spans_both_legs(&mint.key(), &other_mint.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+/// An obligation's USD totals, all in `VALUE_DECIMALS`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct AccountHealth {
+    pub collateral_value: u128,
+    pub liquidation_value: u128,
+    pub borrow_limit: u128,
+    pub debt_value: u128,
+    /// Health factor in bps, `u64::MAX` when there is no debt.
+    pub health_factor: u64,
+    pub liquidatable: bool,
+}
+
+/// What `liquidate` would do to an obligation right now.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct LiquidationPreview {
+    /// Health factor in bps, `u64::MAX` when there is no debt.
+    pub health_factor: u64,
+    pub liquidatable: bool,
+    /// Largest amount of `borrowed_mint` a single liquidation may repay, also what `SETTLE_ALL`
+    /// repays. Zero when not liquidatable.
+    pub max_repay: u64,
+    /// Current liquidation bonus in bps.
+    pub bonus_bps: u64,
+    /// Amount repaid and collateral seized when repaying `max_repay`, after the available-collateral cap.
+    pub repay_for_max: u64,
+    pub collateral_for_max: u64,
+    /// Collateral seized, bonus included, per whole token of `borrowed_mint` repaid.
+    pub collateral_per_token_repaid: u64,
+}
+
+impl <'info>PreviewLiquidation<'info> {
+    /// Runs the same quote as `liquidate` without touching any account, accruing copies of the
+    /// banks and obligation the way `liquidate` accrues the originals. Fixed-term loans count
+    /// towards health but the quote is for the variable-rate debt.
+    pub fn process_preview_liquidation(&self) -> Result<LiquidationPreview>{
+        let borrowed_decimals = self.borrowed_mint.decimals;
+
+        let collateral_price = get_price(&self.collateral_price_update, &self.collateral_bank.oracle_feed_id)?;
+        let borrowed_price = get_price(&self.borrowed_price_update, &self.borrowed_bank.oracle_feed_id)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut user = (*self.user_account).clone();
+        user.accrue_fixed_loans(now)?;
+        let mut collateral_bank = (*self.collateral_bank).clone();
+        collateral_bank.accrue_interest(now)?;
+        let mut borrowed_bank = (*self.borrowed_bank).clone();
+        borrowed_bank.accrue_interest(now)?;
+
+        let terms = LiquidationTerms::compute(
+            Leg::new(&collateral_bank, collateral_price, self.collateral_mint.decimals, &user),
+            Leg::new(&borrowed_bank, borrowed_price, borrowed_decimals, &user),
+            DebtKind::Variable,
+            user.liquidatable_since,
+            now,
+        )?;
+
+        let mut preview = LiquidationPreview {
+            health_factor: terms.health.health_factor(),
+            liquidatable: terms.health.is_liquidatable(),
+            bonus_bps: terms.bonus_rate,
+            ..Default::default()
+        };
+        if !preview.liquidatable {
+            return Ok(preview);
+        }
+
+        let Seizure { repay_amount, collateral_seized, .. } = terms.seize(terms.max_repay)?;
+        preview.max_repay = terms.max_repay;
+        preview.repay_for_max = repay_amount;
+        preview.collateral_for_max = collateral_seized;
+
+        // Uncapped, so the rate reflects prices and the bonus rather than how much collateral is left.
+        let unit = 10u64.checked_pow(borrowed_decimals as u32).ok_or(ErrorCode::MathOverflow)?;
+        let unit_terms = LiquidationTerms { available_collateral: u64::MAX, debt: u64::MAX, ..terms };
+        preview.collateral_per_token_repaid = unit_terms.seize(unit)?.collateral_seized;
+
+        Ok(preview)
+    }
+}
+
+impl <'info>ObligationView<'info> {
+    pub fn process_get_account_health(&self) -> Result<AccountHealth>{
+        let accrued = self.accrued()?;
+        let (leg, other_leg) = self.legs(&accrued)?;
+        let health = Health::compute(&[leg, other_leg])?;
+
+        Ok(AccountHealth {
+            collateral_value: health.collateral_value,
+            liquidation_value: health.liquidation_value,
+            borrow_limit: health.borrow_limit,
+            debt_value: health.debt_value,
+            health_factor: health.health_factor(),
+            liquidatable: health.is_liquidatable(),
+        })
+    }
+
+    /// Largest amount of `mint` that `borrow` would currently accept, bounded by the
+    /// obligation's borrow limit, the bank's idle liquidity and its borrow cap. The origination
+    /// fee is added to the debt, so it comes out of the limit and the cap. Zero below `min_borrow`.
+    pub fn process_get_max_borrow(&self) -> Result<u64>{
+        if self.bank.paused {
+            return Ok(0);
+        }
+
+        let accrued = self.accrued()?;
+        let bank = &accrued.bank;
+        let (leg, other_leg) = self.legs(&accrued)?;
+        let health = Health::compute(&[leg, other_leg])?;
+        let by_health = value_to_amount(health.borrow_headroom(), &leg.price, leg.decimals, false)?;
+
+        let liquidity = bank.liquidity();
+        let by_cap = match bank.borrow_cap {
+            0 => u64::MAX,
+            cap => cap.saturating_sub(bank.total_borrow).saturating_sub(bank.total_fixed_borrow),
+        };
+
+        let max_borrow = amount_before_fee(bank, by_health.min(by_cap))?.min(liquidity);
+        Ok(if max_borrow < bank.min_borrow { 0 } else { max_borrow })
+    }
+
+    /// Largest amount of `mint` the obligation can withdraw while staying within its
+    /// borrow limit, bounded by its deposit and the bank's idle liquidity.
+    pub fn process_get_max_withdraw(&self) -> Result<u64>{
+        if self.bank.paused {
+            return Ok(0);
+        }
+
+        let accrued = self.accrued()?;
+        let bank = &accrued.bank;
+        let (leg, other_leg) = self.legs(&accrued)?;
+        let health = Health::compute(&[leg, other_leg])?;
+        let deposited = bank.deposit_shares_to_amount(leg.deposit_share)?;
+
+        // Each unit withdrawn lowers the borrow limit by its value times this bank's max LTV.
+        let by_health = if health.debt_value == 0 || bank.max_ltv == 0 {
+            deposited
+        } else {
+            let value = health.borrow_headroom()
+                .checked_mul(BPS as u128)
+                .and_then(|v| v.checked_div(bank.max_ltv as u128))
+                .ok_or(ErrorCode::MathOverflow)?;
+            value_to_amount(value, &leg.price, leg.decimals, false)?
+        };
+
+        let liquidity = bank.liquidity();
+
+        Ok(by_health.min(deposited).min(liquidity))
+    }
+
+    /// Copies of both banks and the obligation accrued to now, the way `borrow` and `withdraw`
+    /// accrue the originals before checking health.
+    fn accrued(&self) -> Result<Accrued> {
+        let now = Clock::get()?.unix_timestamp;
+        let mut bank = (*self.bank).clone();
+        bank.accrue_interest(now)?;
+        let mut other_bank = (*self.other_bank).clone();
+        other_bank.accrue_interest(now)?;
+        let mut user = (*self.user_account).clone();
+        user.accrue_fixed_loans(now)?;
+        Ok(Accrued { bank, other_bank, user })
+    }
+
+    fn legs<'a>(&self, accrued: &'a Accrued) -> Result<(Leg<'a>, Leg<'a>)> {
+        let price = get_price(&self.price_update, &accrued.bank.oracle_feed_id)?;
+        let other_price = get_price(&self.other_price_update, &accrued.other_bank.oracle_feed_id)?;
+
+        Ok((
+            Leg::new(&accrued.bank, price, self.mint.decimals, &accrued.user),
+            Leg::new(&accrued.other_bank, other_price, self.other_mint.decimals, &accrued.user),
+        ))
+    }
+}
+
+struct Accrued {
+    bank: Bank,
+    other_bank: Bank,
+    user: User,
+}
+
+/// Largest amount whose debt, origination fee included, fits in `debt`.
+fn amount_before_fee(bank: &Bank, debt: u64) -> Result<u64> {
+    if debt == u64::MAX {
+        return Ok(debt);
+    }
+    let amount = (debt as u128 * BPS as u128 / (BPS + bank.borrow_fee) as u128) as u64;
+    // The fee rounds up, which can push the total one unit over.
+    if amount.checked_add(bank.origination_fee(amount)?).ok_or(ErrorCode::MathOverflow)? > debt {
+        return Ok(amount.saturating_sub(1));
+    }
+    Ok(amount)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::bank;
+
+    #[test]
+    fn amount_before_fee_leaves_room_for_the_fee() {
+        let mut bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        assert_eq!(amount_before_fee(&bank, 1_000).unwrap(), 1_000);
+
+        bank.borrow_fee = 100;
+        for debt in [1_000, 1_010, 1_011, 999_999, 123_456_789] {
+            let amount = amount_before_fee(&bank, debt).unwrap();
+            assert!(amount + bank.origination_fee(amount).unwrap() <= debt);
+            assert!(amount + 1 + bank.origination_fee(amount + 1).unwrap() > debt);
+        }
+        assert_eq!(amount_before_fee(&bank, u64::MAX).unwrap(), u64::MAX);
+    }
+}
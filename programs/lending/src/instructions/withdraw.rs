@@ -1,13 +1,15 @@
-use std::f32::consts::E;
-
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken, 
     token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}
 };
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
 
-use crate::{Bank, User};
+use crate::{Bank, User, SETTLE_ALL};
+use crate::events::WithdrawEvent;
 use crate::error::ErrorCode;
+use crate::health::{Health, Leg};
+use crate::oracle::get_price;
 
 #[derive(Accounts)]
 pub struct Withdraw<'info>{
@@ -30,23 +32,40 @@ pub struct Withdraw<'info>{
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    /// Mint of the obligation's other leg, valued with this one to check health after the withdrawal.
+    pub other_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds=[b"bank", other_mint.key().as_ref()],
+        bump
+    )]
+    pub other_bank: Account<'info, Bank>,
 
     #[account(
         mut,
-        seeds=[b"user", signer.key().as_ref()],
+        seeds=[b"user", user_account.owner.as_ref(), &[user_account.index]],
         bump,
+        constraint = user_account.can_withdraw(&signer.key()) @ ErrorCode::Unauthorized,
+        constraint = user_account.spans_both_legs(&mint.key(), &other_mint.key()) @ ErrorCode::InvalidMint,
     )]
     pub user_account: Account<'info, User>,
 
+    /// Withdrawn funds always go to the obligation owner, even when a delegate signs.
+    #[account(address = user_account.owner)]
+    pub owner: SystemAccount<'info>,
+
     #[account( 
         init_if_needed,
         payer=signer, 
         associated_token::mint = mint, 
-        associated_token::authority = signer,
+        associated_token::authority = owner,
         associated_token::token_program = token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    pub price_update: Account<'info, PriceUpdateV2>,
+    pub other_price_update: Account<'info, PriceUpdateV2>,
+
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -54,36 +73,27 @@ pub struct Withdraw<'info>{
 
 impl <'info>Withdraw<'info> {
     pub fn process_withdraw(&mut self, amount: u64, bumps: &WithdrawBumps )->Result<()> {
-        let user = &mut self.user_account;
-
-        let deposite_value;
-        if self.mint.to_account_info().key() == user.usdc_address{
-            deposite_value=user.deposit_usdc;
-        } else {
-            deposite_value=user.deposit_sol;
-        }
-
-        if amount > deposite_value {
-            return Err(ErrorCode::InsufficientFunds.into());
-        }
-
-        let time_diff = user.last_updated-Clock::get()?.unix_timestamp;
-
-        let bank = &mut self.bank;
-        // Continuous Compound Interest: A = P × e^rt
-        bank.total_deposit = (bank.total_deposit as f64 * E.powf(bank.interest_rate as f32 * time_diff as f32) as f64) as u64;
-
-        let value_per_share = bank.total_deposit as f64 / bank.total_deposit_share as f64;
-
-        let user_value = deposite_value as f64 / value_per_share;
-
-        if user_value < amount as f64 {
-            return Err(ErrorCode::InsufficientFunds.into());
+        require!(!self.bank.paused, ErrorCode::BankPaused);
+        self.user_account.require_fresh()?;
+
+        self.bank.accrue_interest(Clock::get()?.unix_timestamp)?;
+        let price = get_price(&self.price_update, &self.bank.oracle_feed_id)?;
+        let other_price = get_price(&self.other_price_update, &self.other_bank.oracle_feed_id)?;
+        let event = settle_withdraw(&mut self.bank, &mut self.user_account, self.mint.key(), amount, self.signer.key(), &price, &other_price)?;
+
+        // Inside a batch `end_batch` checks the final state instead.
+        let user = &self.user_account;
+        if !user.in_batch {
+            let health = Health::compute(&[
+                Leg::new(&self.bank, price, self.mint.decimals, user),
+                Leg::new(&self.other_bank, other_price, self.other_mint.decimals, user),
+            ])?;
+            require!(health.within_borrow_limit(), ErrorCode::Unhealthy);
         }
 
         let transfer_cpi_accounts = TransferChecked{
             from: self.bank_token_account.to_account_info(),
-            to: self.user_account.to_account_info(),
+            to: self.user_token_account.to_account_info(),
             authority: self.bank_token_account.to_account_info(),
             mint: self.mint.to_account_info()
         };
@@ -101,24 +111,67 @@ impl <'info>Withdraw<'info> {
 
         let decimals = self.mint.decimals; 
 
-        transfer_checked(cpi_ctx, amount, decimals)?;
+        transfer_checked(cpi_ctx, event.amount, decimals)?;
 
-        let bank = &mut self.bank;
+        emit!(event);
 
-        let shares_to_remove = (amount as f64/bank.total_deposit as f64) * bank.total_deposit_share as f64;
-
-        let user = &mut self.user_account;
+        Ok(())
+    }
+}
 
-        if self.mint.to_account_info().key() == user.usdc_address {
-            user.deposit_usdc -= shares_to_remove as u64;
-        } else {
-            user.deposit_sol -= shares_to_remove as u64;
-        }
+/// Burns the deposit shares redeeming `amount` on both the obligation and the bank,
+/// leaving the token transfer to the caller. `SETTLE_ALL` redeems every share held.
+/// `price` and `other_price` are only reported in the event.
+pub(crate) fn settle_withdraw(bank: &mut Account<Bank>, user: &mut Account<User>, mint: Pubkey, amount: u64, signer: Pubkey, price: &Price, other_price: &Price) -> Result<WithdrawEvent> {
+    user.settle_rewards(bank, Clock::get()?.unix_timestamp)?;
 
-        bank.total_deposit -= amount;
-        bank.total_deposit_share -= shares_to_remove as u64;
+    let held_shares = user.deposit_share(&mint);
+    let available = bank.deposit_shares_to_amount(held_shares)?;
 
-        Ok(())
+    // `SETTLE_ALL`, or everything available, redeems every share the obligation holds.
+    let (amount, shares_to_remove) = if amount == SETTLE_ALL || amount == available {
+        (available, held_shares)
+    } else {
+        if amount > available {
+            return Err(ErrorCode::InsufficientFunds.into());
+        }
+        (amount, bank.shares_for_withdraw(amount)?)
+    };
+    require!(shares_to_remove > 0, ErrorCode::ZeroShares);
+    // Shares left that would redeem for nothing are burned with the rest.
+    let shares_to_remove = bank.sweep_deposit_dust(held_shares, shares_to_remove)?;
+
+    // Principal is tracked separately from shares, so redeeming accrued interest can exceed it.
+    if mint == user.usdc_address {
+        user.deposit_usdc_share = user.deposit_usdc_share.checked_sub(shares_to_remove).ok_or(ErrorCode::InsufficientFunds)?;
+        user.deposit_usdc = if user.deposit_usdc_share == 0 { 0 } else { user.deposit_usdc.saturating_sub(amount) };
+    } else {
+        user.deposit_sol_share = user.deposit_sol_share.checked_sub(shares_to_remove).ok_or(ErrorCode::InsufficientFunds)?;
+        user.deposit_sol = if user.deposit_sol_share == 0 { 0 } else { user.deposit_sol.saturating_sub(amount) };
     }
-}
 
+    bank.total_deposit = bank.total_deposit.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_share = bank.total_deposit_share.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+
+    let (user_deposit, user_deposit_share) = match mint {
+        key if key == user.usdc_address => (user.deposit_usdc, user.deposit_usdc_share),
+        _ => (user.deposit_sol, user.deposit_sol_share),
+    };
+
+    Ok(WithdrawEvent {
+        signer,
+        user: user.key(),
+        bank: bank.key(),
+        mint,
+        amount,
+        shares_burned: shares_to_remove,
+        user_deposit,
+        user_deposit_share,
+        bank_total_deposit: bank.total_deposit,
+        bank_total_deposit_share: bank.total_deposit_share,
+        price: price.price,
+        price_exponent: price.exponent,
+        other_price: other_price.price,
+        other_price_exponent: other_price.exponent,
+    })
+}
@@ -1,7 +1,14 @@
 pub mod constants;
 pub mod error;
+pub mod events;
+pub mod health;
 pub mod instructions;
+pub mod oracle;
+pub mod shares;
 pub mod state;
+pub mod swap;
+#[cfg(test)]
+mod test_utils;
 
 use anchor_lang::prelude::*;
 
@@ -15,12 +22,16 @@ declare_id!("3dPENSGTAQ9XCpjUbay57ExdmdrXJakYAYMCWqmuuVwV");
 pub mod lending {
     use super::*;
 
-    pub fn initialize_bank(ctx: Context<InitBank>, liquidation_threshold: u64,max_ltv: u64 ) -> Result<()> {
-        ctx.accounts.process_init_bank(liquidation_threshold, max_ltv)
+    pub fn initialize_bank(ctx: Context<InitBank>, liquidation_threshold: u64,max_ltv: u64, oracle_feed_id: [u8; 32]) -> Result<()> {
+        ctx.accounts.process_init_bank(liquidation_threshold, max_ltv, oracle_feed_id)
     }
 
-    pub fn initialize_user(ctx: Context<InitUser>, usdc_address: Pubkey)->Result<()>{
-        ctx.accounts.process_init_user(usdc_address)
+    pub fn update_bank(ctx: Context<UpdateBank>, params: BankConfigParams) -> Result<()> {
+        ctx.accounts.process_update_bank(params)
+    }
+
+    pub fn initialize_user(ctx: Context<InitUser>, index: u8, usdc_address: Pubkey)->Result<()>{
+        ctx.accounts.process_init_user(index, usdc_address)
     }
 
     pub fn deposit(ctx: Context<Deposit>, amount: u64)->Result<()>{
@@ -35,8 +46,92 @@ pub mod lending {
         ctx.accounts.process_borrow(amount, &ctx.bumps)
     }
 
+    pub fn borrow_fixed(ctx: Context<Borrow>, amount: u64, term: u64) -> Result<()>{
+        ctx.accounts.process_borrow_fixed(amount, term, &ctx.bumps)
+    }
+
     pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()>{
         ctx.accounts.process_repay(amount)
     }
 
+    pub fn repay_fixed(ctx: Context<Repay>, amount: u64) -> Result<()>{
+        ctx.accounts.process_repay_fixed(amount)
+    }
+
+    pub fn repay_for(ctx: Context<RepayFor>, amount: u64) -> Result<()>{
+        ctx.accounts.process_repay_for(amount)
+    }
+
+    pub fn liquidate(ctx: Context<Liquidate>, repay_amount: u64) -> Result<()>{
+        ctx.accounts.process_liquidate(repay_amount, &ctx.bumps)
+    }
+
+    pub fn liquidate_fixed(ctx: Context<Liquidate>, repay_amount: u64) -> Result<()>{
+        ctx.accounts.process_liquidate_fixed(repay_amount, &ctx.bumps)
+    }
+
+    pub fn leverage<'info>(ctx: Context<'_, '_, '_, 'info, Leverage<'info>>, deposit_amount: u64, borrow_amount: u64, min_amount_out: u64) -> Result<()> {
+        ctx.accounts.process_leverage(deposit_amount, borrow_amount, min_amount_out, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn swap_collateral<'info>(ctx: Context<'_, '_, '_, 'info, SwapCollateral<'info>>, amount: u64, min_amount_out: u64) -> Result<()> {
+        ctx.accounts.process_swap_collateral(amount, min_amount_out, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn refinance_debt<'info>(ctx: Context<'_, '_, '_, 'info, RefinanceDebt<'info>>, borrow_amount: u64, min_amount_out: u64) -> Result<()> {
+        ctx.accounts.process_refinance_debt(borrow_amount, min_amount_out, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn start_batch(ctx: Context<StartBatch>) -> Result<()> {
+        ctx.accounts.process_start_batch()
+    }
+
+    pub fn end_batch(ctx: Context<EndBatch>) -> Result<()> {
+        ctx.accounts.process_end_batch()
+    }
+
+    pub fn migrate_account(ctx: Context<MigrateAccount>) -> Result<()> {
+        ctx.accounts.process_migrate_account()
+    }
+
+    pub fn handle_bad_debt(ctx: Context<HandleBadDebt>) -> Result<()> {
+        ctx.accounts.process_handle_bad_debt()
+    }
+
+    pub fn add_reward(ctx: Context<AddReward>, slot: u8, side: RewardSide, emission_per_second: u64, start_time: i64, end_time: i64) -> Result<()> {
+        ctx.accounts.process_add_reward(slot, side, emission_per_second, start_time, end_time)
+    }
+
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        ctx.accounts.process_claim_rewards(&ctx.bumps)
+    }
+
+    pub fn refresh_bank(ctx: Context<RefreshBank>) -> Result<()> {
+        ctx.accounts.process_refresh_bank()
+    }
+
+    pub fn refresh_obligation(ctx: Context<RefreshObligation>) -> Result<()> {
+        ctx.accounts.process_refresh_obligation()
+    }
+
+    pub fn preview_liquidation(ctx: Context<PreviewLiquidation>) -> Result<LiquidationPreview> {
+        ctx.accounts.process_preview_liquidation()
+    }
+
+    pub fn get_account_health(ctx: Context<ObligationView>) -> Result<AccountHealth> {
+        ctx.accounts.process_get_account_health()
+    }
+
+    pub fn get_max_borrow(ctx: Context<ObligationView>) -> Result<u64> {
+        ctx.accounts.process_get_max_borrow()
+    }
+
+    pub fn get_max_withdraw(ctx: Context<ObligationView>) -> Result<u64> {
+        ctx.accounts.process_get_max_withdraw()
+    }
+
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Pubkey, can_borrow: bool, can_withdraw: bool) -> Result<()>{
+        ctx.accounts.process_set_delegate(delegate, can_borrow, can_withdraw)
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::error::GetPriceError;
+use pyth_solana_receiver_sdk::price_update::{FeedId, Price, PriceUpdateV2};
+
+use crate::constants::MAXIMUM_AGE;
+use crate::error::ErrorCode;
+
+/// Reads a fresh, positive price for `feed_id` and maps Pyth failures onto our own error codes.
+/// Callers pass the feed of the bank being valued, see `Bank::oracle_feed_id`.
+pub fn get_price(price_update: &PriceUpdateV2, feed_id: &FeedId) -> Result<Price> {
+    require!(*feed_id != FeedId::default(), ErrorCode::InvalidOracle);
+    let price = price_update
+        .get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, feed_id)
+        .map_err(|err| match err {
+            GetPriceError::PriceTooOld => ErrorCode::StaleOracle,
+            _ => ErrorCode::InvalidOracle,
+        })?;
+
+    require!(price.price > 0, ErrorCode::InvalidOracle);
+    Ok(price)
+}
@@ -0,0 +1,77 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+
+/// Which way a conversion rounds. Callers pick the direction that favors the protocol:
+/// fewer shares minted and more burned, less paid out and more owed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// Shares worth `amount` in a pool holding `total_amount` across `total_shares`. An empty pool mints 1:1.
+pub fn to_shares(amount: u64, total_amount: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    if total_shares == 0 {
+        return Ok(amount);
+    }
+    mul_div(amount, total_shares, total_amount, rounding)
+}
+
+/// Amount backing `shares` in a pool holding `total_amount` across `total_shares`.
+pub fn to_amount(shares: u64, total_amount: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    if total_shares == 0 {
+        return Ok(0);
+    }
+    mul_div(shares, total_amount, total_shares, rounding)
+}
+
+fn mul_div(value: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    require!(denominator > 0, ErrorCode::MathOverflow);
+    let product = (value as u128)
+        .checked_mul(numerator as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let quotient = match rounding {
+        Rounding::Down => product / denominator as u128,
+        Rounding::Up => product.div_ceil(denominator as u128),
+    };
+    u64::try_from(quotient).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_pool_converts_one_to_one() {
+        assert_eq!(to_shares(500, 0, 0, Rounding::Down).unwrap(), 500);
+        assert_eq!(to_amount(500, 0, 0, Rounding::Up).unwrap(), 0);
+    }
+
+    #[test]
+    fn rounding_follows_the_requested_direction() {
+        // 3 tokens back 2 shares, so 1 share is worth 1.5 tokens.
+        assert_eq!(to_shares(1, 3, 2, Rounding::Down).unwrap(), 0);
+        assert_eq!(to_shares(1, 3, 2, Rounding::Up).unwrap(), 1);
+        assert_eq!(to_amount(1, 3, 2, Rounding::Down).unwrap(), 1);
+        assert_eq!(to_amount(1, 3, 2, Rounding::Up).unwrap(), 2);
+        // Exact conversions don't round either way.
+        assert_eq!(to_shares(3, 3, 2, Rounding::Up).unwrap(), 2);
+        assert_eq!(to_amount(2, 3, 2, Rounding::Down).unwrap(), 3);
+    }
+
+    #[test]
+    fn round_trip_never_pays_out_more_than_deposited() {
+        let (total_amount, total_shares) = (1_000_003, 999_999);
+        for amount in [1, 7, 1_000, 123_456] {
+            let shares = to_shares(amount, total_amount, total_shares, Rounding::Down).unwrap();
+            assert!(to_amount(shares, total_amount, total_shares, Rounding::Down).unwrap() <= amount);
+        }
+    }
+
+    #[test]
+    fn overflow_and_empty_denominator_are_errors() {
+        assert!(to_shares(1, 0, 1, Rounding::Down).is_err());
+        assert!(to_amount(u64::MAX, u64::MAX, 1, Rounding::Down).is_err());
+    }
+}
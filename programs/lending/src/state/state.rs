@@ -1,9 +1,14 @@
 use anchor_lang::prelude::*;
 
+use crate::constants::{BANK_RESERVED, BONUS_FULL_SHORTFALL, BPS, MAX_REWARDS, REWARD_INDEX_SCALE, SECONDS_PER_YEAR, USER_RESERVED, VIRTUAL_DEPOSIT};
+use crate::error::ErrorCode;
+use crate::shares::{to_amount, to_shares, Rounding};
+
 #[account]
 #[derive(InitSpace)]
 pub struct User {
     pub owner: Pubkey,
+    pub index: u8,
     pub deposit_sol: u64,
     pub deposit_sol_share: u64,
     pub borrow_sol: u64,
@@ -13,7 +18,285 @@ pub struct User {
     pub borrow_usdc: u64,
     pub borrow_usdc_share: u64,
     pub usdc_address: Pubkey,
-    pub last_updated: i64
+    /// Mint backing the `*_sol` fields, bound on first use and re-bindable once that leg is empty.
+    pub sol_address: Pubkey,
+    pub last_updated: i64,
+    pub last_updated_borrowed: i64,
+    /// Key allowed to act on this obligation besides the owner, `Pubkey::default()` when unset.
+    pub delegate: Pubkey,
+    pub delegate_can_borrow: bool,
+    pub delegate_can_withdraw: bool,
+    /// When this obligation was first seen liquidatable by a refresh or liquidation, zero while healthy.
+    pub liquidatable_since: i64,
+    /// Slot of the last `refresh_obligation`, the cached values below are as of this slot.
+    pub last_refresh_slot: u64,
+    pub collateral_value: u128,
+    pub liquidation_value: u128,
+    pub borrow_limit: u128,
+    pub debt_value: u128,
+    /// Reward progress per leg, indexed like the leg's `Bank::rewards`.
+    pub usdc_rewards: [UserReward; MAX_REWARDS],
+    pub sol_rewards: [UserReward; MAX_REWARDS],
+    /// Fixed-term loans per leg, separate from the variable-rate `borrow_*` shares.
+    pub fixed_usdc: FixedLoan,
+    pub fixed_sol: FixedLoan,
+    /// Set between `start_batch` and `end_batch`, which defers borrow and withdraw health checks to `end_batch`.
+    pub in_batch: bool,
+    /// `ACCOUNT_VERSION` once initialized or migrated.
+    pub version: u8,
+    pub reserved: [u8; USER_RESERVED],
+}
+
+/// A loan whose rate was locked at origination. Interest is simple and keeps
+/// accruing at the same rate past `maturity` until the loan is repaid.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct FixedLoan {
+    pub principal: u64,
+    /// Interest accrued up to `last_accrued` and not yet repaid.
+    pub interest: u64,
+    /// Annual rate in bps.
+    pub rate: u64,
+    pub last_accrued: i64,
+    pub maturity: i64,
+    /// Fraction of a unit of interest carried to the next accrual, scaled by `BPS * SECONDS_PER_YEAR`.
+    pub interest_remainder: u64,
+}
+
+impl FixedLoan {
+    pub fn is_active(&self) -> bool {
+        self.principal > 0 || self.interest > 0
+    }
+
+    /// Debt as of the last accrual, with a carried fraction of a unit rounded up.
+    pub fn owed(&self) -> u64 {
+        self.principal.saturating_add(self.interest_due())
+    }
+
+    fn interest_due(&self) -> u64 {
+        self.interest.saturating_add((self.interest_remainder > 0) as u64)
+    }
+
+    pub fn is_overdue(&self, now: i64, grace_period: u64) -> bool {
+        self.is_active() && now > self.maturity.saturating_add(grace_period as i64)
+    }
+
+    /// Accrues interest on the principal since `last_accrued`. Fractions of a unit carry over
+    /// to the next accrual, so how often a loan is accrued doesn't change what it owes.
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_accrued);
+        if self.principal > 0 && elapsed > 0 {
+            let denominator = BPS as u128 * SECONDS_PER_YEAR as u128;
+            let scaled = (self.principal as u128)
+                .checked_mul(self.rate as u128)
+                .and_then(|v| v.checked_mul(elapsed as u128))
+                .and_then(|v| v.checked_add(self.interest_remainder as u128))
+                .ok_or(ErrorCode::MathOverflow)?;
+            let interest = u64::try_from(scaled / denominator).map_err(|_| ErrorCode::MathOverflow)?;
+            self.interest = self.interest.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+            self.interest_remainder = (scaled % denominator) as u64;
+        }
+        self.last_accrued = self.last_accrued.max(now);
+        Ok(())
+    }
+
+    /// Applies `amount` to interest first, then principal. Returns `(interest_paid, principal_paid)`.
+    /// A carried fraction is charged as a whole unit once the rest of the interest is paid.
+    pub fn repay(&mut self, amount: u64) -> Result<(u64, u64)> {
+        require!(amount <= self.owed(), ErrorCode::OverBorrowableAmount);
+        let interest_due = self.interest_due();
+        let interest_paid = amount.min(interest_due);
+        let principal_paid = amount - interest_paid;
+        if interest_paid == interest_due {
+            self.interest = 0;
+            self.interest_remainder = 0;
+        } else {
+            self.interest -= interest_paid;
+        }
+        self.principal -= principal_paid;
+        if !self.is_active() {
+            *self = FixedLoan::default();
+        }
+        Ok((interest_paid, principal_paid))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct UserReward {
+    /// `RewardInfo::index` as of this obligation's last settlement.
+    pub index: u128,
+    /// Rewards earned and not yet claimed.
+    pub accrued: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub enum RewardSide {
+    #[default]
+    Deposit,
+    Borrow,
+}
+
+/// A liquidity-mining program paying `mint` out of its vault to a bank's depositors or borrowers.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct RewardInfo {
+    /// `Pubkey::default()` marks an unused slot.
+    pub mint: Pubkey,
+    pub side: RewardSide,
+    pub emission_per_second: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    /// Rewards emitted per share so far, scaled by `REWARD_INDEX_SCALE`.
+    pub index: u128,
+    pub last_updated: i64,
+}
+
+impl User {
+    pub fn is_owner_or_delegate(&self, key: &Pubkey) -> bool {
+        *key == self.owner || (self.delegate != Pubkey::default() && *key == self.delegate)
+    }
+
+    pub fn can_borrow(&self, key: &Pubkey) -> bool {
+        *key == self.owner || (self.delegate_can_borrow && self.is_owner_or_delegate(key))
+    }
+
+    pub fn can_withdraw(&self, key: &Pubkey) -> bool {
+        *key == self.owner || (self.delegate_can_withdraw && self.is_owner_or_delegate(key))
+    }
+
+    /// Whether `mint` maps onto one of this obligation's two legs.
+    pub fn accepts_mint(&self, mint: &Pubkey) -> bool {
+        *mint == self.usdc_address || *mint == self.sol_address || self.sol_leg_is_empty()
+    }
+
+    /// Whether `mint` is a leg this obligation is already bound to, unlike `accepts_mint`
+    /// which also lets an empty non-USDC leg be rebound.
+    pub fn is_bound_to(&self, mint: &Pubkey) -> bool {
+        *mint == self.usdc_address || (*mint == self.sol_address && self.sol_address != Pubkey::default())
+    }
+
+    /// Whether `a` and `b` are this obligation's two distinct legs, one of them USDC.
+    pub fn spans_both_legs(&self, a: &Pubkey, b: &Pubkey) -> bool {
+        (*a == self.usdc_address) != (*b == self.usdc_address) && self.accepts_mint(a) && self.accepts_mint(b)
+    }
+
+    pub fn has_collateral(&self) -> bool {
+        self.deposit_usdc_share > 0 || self.deposit_sol_share > 0
+    }
+
+    pub fn sol_leg_is_empty(&self) -> bool {
+        self.deposit_sol_share == 0 && self.borrow_sol_share == 0 && !self.fixed_sol.is_active()
+    }
+
+    /// Binds the non-USDC leg to `bank`'s mint so later valuations always use the same bank.
+    /// Must run before `settle_rewards` on `bank`. Rebinding starts the leg's reward progress
+    /// at `bank`'s indices, the old bank's indices mean nothing there.
+    pub fn bind_mint(&mut self, bank: &Bank) -> Result<()> {
+        let mint = bank.mint_address;
+        if mint == self.usdc_address || mint == self.sol_address {
+            return Ok(());
+        }
+        let sol_address = self.sol_address;
+        self.rebind_leg(&sol_address, bank)
+    }
+
+    /// Moves the leg holding `mint` over to `bank`'s mint, so an instruction that empties a leg
+    /// can reuse it for a mint the obligation doesn't hold yet. Either leg can move, the USDC
+    /// one included. The leg must be empty with its rewards claimed.
+    pub fn rebind_leg(&mut self, mint: &Pubkey, bank: &Bank) -> Result<()> {
+        let is_empty = self.deposit_share(mint) == 0 && self.borrow_share(mint) == 0 && !self.fixed_loan(mint).is_active();
+        require!(is_empty, ErrorCode::InvalidMint);
+        let rewards = self.rewards_mut(mint);
+        require!(rewards.iter().all(|reward| reward.accrued == 0), ErrorCode::UnclaimedRewards);
+        for (reward, user_reward) in bank.rewards.iter().zip(rewards.iter_mut()) {
+            *user_reward = UserReward { index: reward.index, accrued: 0 };
+        }
+        match *mint {
+            key if key == self.usdc_address => self.usdc_address = bank.mint_address,
+            _ => self.sol_address = bank.mint_address,
+        }
+        Ok(())
+    }
+
+    /// The leg other than `mint`'s, `Pubkey::default()` while the non-USDC leg is unbound.
+    pub fn other_leg(&self, mint: &Pubkey) -> Pubkey {
+        match *mint {
+            key if key == self.usdc_address => self.sol_address,
+            _ => self.usdc_address,
+        }
+    }
+
+    /// Risky instructions must run after `refresh_obligation` in the same slot.
+    pub fn require_fresh(&self) -> Result<()> {
+        require!(self.last_refresh_slot == Clock::get()?.slot, ErrorCode::StaleObligation);
+        Ok(())
+    }
+
+    pub fn deposit_share(&self, mint: &Pubkey) -> u64 {
+        match *mint {
+            key if key == self.usdc_address => self.deposit_usdc_share,
+            _ => self.deposit_sol_share,
+        }
+    }
+
+    pub fn borrow_share(&self, mint: &Pubkey) -> u64 {
+        match *mint {
+            key if key == self.usdc_address => self.borrow_usdc_share,
+            _ => self.borrow_sol_share,
+        }
+    }
+
+    pub fn fixed_loan(&self, mint: &Pubkey) -> &FixedLoan {
+        match *mint {
+            key if key == self.usdc_address => &self.fixed_usdc,
+            _ => &self.fixed_sol,
+        }
+    }
+
+    pub fn fixed_loan_mut(&mut self, mint: &Pubkey) -> &mut FixedLoan {
+        match *mint {
+            key if key == self.usdc_address => &mut self.fixed_usdc,
+            _ => &mut self.fixed_sol,
+        }
+    }
+
+    pub fn accrue_fixed_loans(&mut self, now: i64) -> Result<()> {
+        self.fixed_usdc.accrue(now)?;
+        self.fixed_sol.accrue(now)
+    }
+
+    pub fn rewards_mut(&mut self, mint: &Pubkey) -> &mut [UserReward; MAX_REWARDS] {
+        match *mint {
+            key if key == self.usdc_address => &mut self.usdc_rewards,
+            _ => &mut self.sol_rewards,
+        }
+    }
+
+    /// Accrues `bank`'s reward programs and credits this obligation for its current shares.
+    /// Must run before any of the obligation's shares in `bank` change.
+    pub fn settle_rewards(&mut self, bank: &mut Bank, now: i64) -> Result<()> {
+        bank.accrue_rewards(now)?;
+
+        let mint = bank.mint_address;
+        let deposit_share = self.deposit_share(&mint);
+        let borrow_share = self.borrow_share(&mint);
+        for (reward, user_reward) in bank.rewards.iter().zip(self.rewards_mut(&mint).iter_mut()) {
+            if reward.mint == Pubkey::default() {
+                continue;
+            }
+            let shares = match reward.side {
+                RewardSide::Deposit => deposit_share,
+                RewardSide::Borrow => borrow_share,
+            };
+            let earned = reward.index
+                .checked_sub(user_reward.index)
+                .and_then(|delta| delta.checked_mul(shares as u128))
+                .map(|v| v / REWARD_INDEX_SCALE)
+                .and_then(|v| u64::try_from(v).ok())
+                .ok_or(ErrorCode::MathOverflow)?;
+            user_reward.accrued = user_reward.accrued.checked_add(earned).ok_or(ErrorCode::MathOverflow)?;
+            user_reward.index = reward.index;
+        }
+        Ok(())
+    }
 }
 
 #[account]
@@ -25,10 +308,465 @@ pub struct Bank{
     pub total_deposit_share: u64,
     pub total_borrow: u64,
     pub total_borrow_share: u64,
+    /// Risk parameters below are in basis points.
     pub liquity_threshold: u64,
     pub liquity_bonus: u64,
     pub liquity_close_factor: u64,
     pub max_ltv: u64,
+    /// `liquity_bonus` is the bonus paid at health 1.0, growing towards this as health falls.
+    pub liquity_max_bonus: u64,
+    /// Seconds for the bonus to ramp to `liquity_max_bonus` once an obligation stays liquidatable, zero disables.
+    pub liquity_bonus_ramp: u64,
+    /// Share of the liquidation bonus, in bps, kept as `protocol_fees` instead of paid to the liquidator.
+    pub liquity_protocol_fee: u64,
+    /// Unix timestamp interest was last accrued at.
     pub last_updated: u64,
+    /// Annual borrow rate in bps, paid by borrowers to depositors.
     pub interest_rate: u64,
+    pub paused: bool,
+    /// Upper bound on `total_deposit`, zero means uncapped.
+    pub deposit_cap: u64,
+    /// Upper bound on `total_borrow` plus `total_fixed_borrow`, zero means uncapped.
+    pub borrow_cap: u64,
+    /// Treasury tokens owned by the protocol rather than depositors, first in line to absorb bad debt.
+    pub protocol_fees: u64,
+    /// Smallest amount a single borrow may take, zero disables.
+    pub min_borrow: u64,
+    /// Smallest debt an obligation may keep open in this bank, zero disables.
+    pub min_debt: u64,
+    pub rewards: [RewardInfo; MAX_REWARDS],
+    /// Origination fee in bps of each borrow, added to the borrower's debt.
+    pub borrow_fee: u64,
+    /// Share of the origination fee, in bps, paid to the referrer when one is passed.
+    pub referrer_fee_share: u64,
+    /// Principal lent out through fixed-term loans, not part of `total_borrow`.
+    pub total_fixed_borrow: u64,
+    /// Added to `interest_rate` to price fixed-term loans, in bps.
+    pub fixed_rate_premium: u64,
+    /// Longest fixed term in seconds, zero disables fixed-term borrowing.
+    pub max_fixed_term: u64,
+    /// Seconds after maturity before an unpaid fixed-term loan becomes liquidatable.
+    pub fixed_grace_period: u64,
+    /// Pyth feed pricing this bank's mint, every leg on this bank is valued with it.
+    pub oracle_feed_id: [u8; 32],
+    /// `ACCOUNT_VERSION` once initialized or migrated.
+    pub version: u8,
+    pub reserved: [u8; BANK_RESERVED],
+}
+
+impl Bank {
+    /// Bps risk parameters must be ordered `max_ltv <= liquity_threshold <= 100%`.
+    pub fn validate_config(&self) -> Result<()> {
+        require!(self.max_ltv <= self.liquity_threshold, ErrorCode::InvalidConfig);
+        require!(self.liquity_threshold <= BPS, ErrorCode::InvalidConfig);
+        require!(self.liquity_close_factor <= BPS, ErrorCode::InvalidConfig);
+        require!(self.liquity_bonus <= self.liquity_max_bonus, ErrorCode::InvalidConfig);
+        require!(self.liquity_max_bonus <= BPS, ErrorCode::InvalidConfig);
+        require!(self.liquity_protocol_fee <= BPS, ErrorCode::InvalidConfig);
+        require!(self.borrow_fee <= BPS, ErrorCode::InvalidConfig);
+        require!(self.referrer_fee_share <= BPS, ErrorCode::InvalidConfig);
+        require!(self.oracle_feed_id != [0; 32], ErrorCode::InvalidConfig);
+        Ok(())
+    }
+
+    /// Liquidation bonus in bps for an obligation at `health_factor` that has been
+    /// liquidatable for `seconds_liquidatable`. Scales linearly from `liquity_bonus`
+    /// at health 1.0 to `liquity_max_bonus` at `BONUS_FULL_SHORTFALL` below it, then
+    /// ramps the rest of the way to the max over `liquity_bonus_ramp` seconds.
+    /// A larger bonus raises `liquidation_break_even`, below which liquidations are allowed
+    /// without improving health, so deep shortfalls always stay clearable.
+    pub fn liquidation_bonus(&self, health_factor: u64, seconds_liquidatable: i64) -> u64 {
+        let spread = self.liquity_max_bonus.saturating_sub(self.liquity_bonus);
+        let shortfall = BPS.saturating_sub(health_factor).min(BONUS_FULL_SHORTFALL);
+        let mut bonus = self.liquity_bonus + spread * shortfall / BONUS_FULL_SHORTFALL;
+
+        if self.liquity_bonus_ramp > 0 && seconds_liquidatable > 0 {
+            let elapsed = (seconds_liquidatable as u64).min(self.liquity_bonus_ramp);
+            let remaining = self.liquity_max_bonus.saturating_sub(bonus);
+            bonus += (remaining as u128 * elapsed as u128 / self.liquity_bonus_ramp as u128) as u64;
+        }
+        bonus
+    }
+
+    /// Health factor in bps below which seizing collateral at `bonus` bps lowers health faster
+    /// than the repayment raises it: `liquity_threshold * (1 + bonus)`.
+    pub fn liquidation_break_even(&self, bonus: u64) -> u64 {
+        (self.liquity_threshold as u128 * (BPS + bonus) as u128 / BPS as u128) as u64
+    }
+
+    /// Accrues simple interest on `total_borrow` since `last_updated` and credits it to depositors.
+    /// Borrowers are charged rounded up, depositors credited rounded down.
+    pub fn accrue_interest(&mut self, now: i64) -> Result<()> {
+        let now = u64::try_from(now).map_err(|_| ErrorCode::MathOverflow)?;
+        let elapsed = now.saturating_sub(self.last_updated);
+        if self.last_updated == 0 || elapsed == 0 || self.total_borrow == 0 {
+            self.last_updated = now;
+            return Ok(());
+        }
+
+        let numerator = (self.total_borrow as u128)
+            .checked_mul(self.interest_rate as u128)
+            .and_then(|v| v.checked_mul(elapsed as u128))
+            .ok_or(ErrorCode::MathOverflow)?;
+        let denominator = BPS as u128 * SECONDS_PER_YEAR as u128;
+        let earned = u64::try_from(numerator / denominator).map_err(|_| ErrorCode::MathOverflow)?;
+        let owed = u64::try_from(numerator.div_ceil(denominator)).map_err(|_| ErrorCode::MathOverflow)?;
+
+        self.total_borrow = self.total_borrow.checked_add(owed).ok_or(ErrorCode::MathOverflow)?;
+        self.total_deposit = self.total_deposit.checked_add(earned).ok_or(ErrorCode::MathOverflow)?;
+        self.last_updated = now;
+        Ok(())
+    }
+
+    /// Advances every reward index over the part of `[last_updated, now]` inside its program's
+    /// window. Emissions while nobody holds shares on the rewarded side are not paid out.
+    pub fn accrue_rewards(&mut self, now: i64) -> Result<()> {
+        let (total_deposit_share, total_borrow_share) = (self.total_deposit_share, self.total_borrow_share);
+        for reward in self.rewards.iter_mut() {
+            if reward.mint == Pubkey::default() {
+                continue;
+            }
+            let from = reward.last_updated.max(reward.start_time);
+            let to = now.min(reward.end_time);
+            let total_shares = match reward.side {
+                RewardSide::Deposit => total_deposit_share,
+                RewardSide::Borrow => total_borrow_share,
+            };
+            if to > from && total_shares > 0 {
+                let delta = (reward.emission_per_second as u128)
+                    .checked_mul((to - from) as u128)
+                    .and_then(|v| v.checked_mul(REWARD_INDEX_SCALE))
+                    .map(|v| v / total_shares as u128)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                reward.index = reward.index.checked_add(delta).ok_or(ErrorCode::MathOverflow)?;
+            }
+            reward.last_updated = reward.last_updated.max(now);
+        }
+        Ok(())
+    }
+
+    pub fn reward_slot(&self, mint: &Pubkey) -> Result<usize> {
+        self.rewards
+            .iter()
+            .position(|reward| reward.mint != Pubkey::default() && reward.mint == *mint)
+            .ok_or(ErrorCode::RewardNotFound.into())
+    }
+
+    pub fn check_deposit_cap(&self, amount: u64) -> Result<()> {
+        let new_total = self.total_deposit.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        require!(self.deposit_cap == 0 || new_total <= self.deposit_cap, ErrorCode::CapExceeded);
+        Ok(())
+    }
+
+    pub fn check_borrow_cap(&self, amount: u64) -> Result<()> {
+        let new_total = self.total_borrow
+            .checked_add(self.total_fixed_borrow)
+            .and_then(|v| v.checked_add(amount))
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(self.borrow_cap == 0 || new_total <= self.borrow_cap, ErrorCode::CapExceeded);
+        Ok(())
+    }
+
+    /// Deposit shares minted for depositing `amount`, rounded down.
+    pub fn shares_for_deposit(&self, amount: u64) -> Result<u64> {
+        let (total, total_shares) = self.virtual_deposit_totals()?;
+        to_shares(amount, total, total_shares, Rounding::Down)
+    }
+
+    /// Deposit shares burned for withdrawing `amount`, rounded up.
+    pub fn shares_for_withdraw(&self, amount: u64) -> Result<u64> {
+        let (total, total_shares) = self.virtual_deposit_totals()?;
+        to_shares(amount, total, total_shares, Rounding::Up)
+    }
+
+    /// Current value of `shares` deposit shares, rounded down.
+    pub fn deposit_shares_to_amount(&self, shares: u64) -> Result<u64> {
+        let (total, total_shares) = self.virtual_deposit_totals()?;
+        to_amount(shares, total, total_shares, Rounding::Down)
+    }
+
+    /// Borrow shares minted for borrowing `amount`, rounded up.
+    pub fn shares_for_borrow(&self, amount: u64) -> Result<u64> {
+        to_shares(amount, self.total_borrow, self.total_borrow_share, Rounding::Up)
+    }
+
+    /// Borrow shares burned for repaying `amount`, rounded down.
+    pub fn shares_for_repay(&self, amount: u64) -> Result<u64> {
+        to_shares(amount, self.total_borrow, self.total_borrow_share, Rounding::Down)
+    }
+
+    /// Current debt owed for `shares` borrow shares, rounded up.
+    pub fn borrow_shares_to_amount(&self, shares: u64) -> Result<u64> {
+        to_amount(shares, self.total_borrow, self.total_borrow_share, Rounding::Up)
+    }
+
+    /// Annual rate locked into a fixed-term loan originated now.
+    pub fn fixed_rate(&self) -> Result<u64> {
+        self.interest_rate.checked_add(self.fixed_rate_premium).ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Idle treasury liquidity available to borrow or withdraw.
+    pub fn liquidity(&self) -> u64 {
+        self.total_deposit.saturating_sub(self.total_borrow).saturating_sub(self.total_fixed_borrow)
+    }
+
+    /// Origination fee charged on borrowing `amount`, rounded up.
+    pub fn origination_fee(&self, amount: u64) -> Result<u64> {
+        let fee = (amount as u128)
+            .checked_mul(self.borrow_fee as u128)
+            .map(|v| v.div_ceil(BPS as u128))
+            .ok_or(ErrorCode::MathOverflow)?;
+        u64::try_from(fee).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// An obligation keeping `shares` borrow shares must owe nothing or at least `min_debt`.
+    pub fn check_min_debt(&self, shares: u64) -> Result<()> {
+        self.check_min_debt_amount(self.borrow_shares_to_amount(shares)?)
+    }
+
+    /// Same as `check_min_debt` for a debt already in tokens, e.g. a fixed-term loan's `owed()`.
+    pub fn check_min_debt_amount(&self, debt: u64) -> Result<()> {
+        require!(debt == 0 || debt >= self.min_debt, ErrorCode::DebtBelowMinimum);
+        Ok(())
+    }
+
+    /// Deposit shares to burn when redeeming `shares` out of `held`, widened to all of
+    /// `held` when the remainder would redeem for nothing.
+    pub fn sweep_deposit_dust(&self, held: u64, shares: u64) -> Result<u64> {
+        let remaining = held.saturating_sub(shares);
+        if remaining > 0 && self.deposit_shares_to_amount(remaining)? == 0 {
+            return Ok(held);
+        }
+        Ok(shares)
+    }
+
+    /// Borrow shares to burn when repaying `shares` out of `held`, widened to all of `held`
+    /// when the remainder is worth less than one token unit.
+    pub fn sweep_borrow_dust(&self, held: u64, shares: u64) -> Result<u64> {
+        let remaining = held.saturating_sub(shares);
+        if remaining > 0 && to_amount(remaining, self.total_borrow, self.total_borrow_share, Rounding::Down)? == 0 {
+            return Ok(held);
+        }
+        Ok(shares)
+    }
+
+    /// Deposit totals including `VIRTUAL_DEPOSIT`. Borrow conversions don't need it since
+    /// debt can only be created by borrowing, never donated.
+    fn virtual_deposit_totals(&self) -> Result<(u64, u64)> {
+        let total = self.total_deposit.checked_add(VIRTUAL_DEPOSIT).ok_or(ErrorCode::MathOverflow)?;
+        let total_shares = self.total_deposit_share.checked_add(VIRTUAL_DEPOSIT).ok_or(ErrorCode::MathOverflow)?;
+        Ok((total, total_shares))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{bank, user, zeroed};
+
+    #[test]
+    fn liquidation_bonus_scales_with_shortfall() {
+        let bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        assert_eq!(bank.liquidation_bonus(9_999, 0), 500);
+        assert_eq!(bank.liquidation_bonus(9_500, 0), 750);
+        assert_eq!(bank.liquidation_bonus(9_000, 0), 1_000);
+        // Deep shortfalls get the max bonus, and at that bonus they're below break-even.
+        assert_eq!(bank.liquidation_bonus(7_000, 0), 1_000);
+        assert_eq!(bank.liquidation_break_even(1_000), 8_800);
+    }
+
+    #[test]
+    fn rebinding_starts_rewards_at_the_new_bank_index() {
+        let reward = |index| RewardInfo { mint: Pubkey::new_unique(), index, ..Default::default() };
+        let mut old_bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        old_bank.rewards[0] = reward(5 * REWARD_INDEX_SCALE);
+        let mut new_bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        new_bank.rewards[0] = reward(REWARD_INDEX_SCALE);
+
+        let mut user = user(Pubkey::new_unique(), old_bank.mint_address);
+        user.deposit_sol_share = 10;
+        user.settle_rewards(&mut old_bank, 0).unwrap();
+        assert_eq!(user.sol_rewards[0].index, 5 * REWARD_INDEX_SCALE);
+
+        assert_eq!(user.sol_rewards[0].accrued, 50);
+
+        // Withdrawn and claimed, the leg is free to move.
+        user.deposit_sol_share = 0;
+        user.sol_rewards[0].accrued = 0;
+        assert!(user.accepts_mint(&new_bank.mint_address));
+        // Only a bound leg's rewards can be claimed, any other bank would overwrite its indices.
+        assert!(!user.is_bound_to(&new_bank.mint_address));
+        user.bind_mint(&new_bank).unwrap();
+        assert_eq!(user.sol_address, new_bank.mint_address);
+        assert!(user.is_bound_to(&new_bank.mint_address));
+        assert!(!user.is_bound_to(&old_bank.mint_address));
+        assert_eq!(user.sol_rewards[0].index, REWARD_INDEX_SCALE);
+        user.settle_rewards(&mut new_bank, 0).unwrap();
+        assert_eq!(user.sol_rewards[0].accrued, 0);
+    }
+
+    #[test]
+    fn an_emptied_usdc_leg_can_move_to_another_mint() {
+        let (usdc, sol) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let stable_bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        let mut user = user(usdc, sol);
+        user.deposit_sol_share = 10;
+        user.borrow_usdc_share = 5;
+        assert!(user.rebind_leg(&usdc, &stable_bank).is_err());
+
+        user.borrow_usdc_share = 0;
+        user.rebind_leg(&usdc, &stable_bank).unwrap();
+        assert_eq!((user.usdc_address, user.sol_address), (stable_bank.mint_address, sol));
+        assert_eq!(user.other_leg(&stable_bank.mint_address), sol);
+        // The SOL leg still holds collateral, so it can't move.
+        assert!(user.rebind_leg(&sol, &bank(Pubkey::new_unique(), 8_000, 7_500)).is_err());
+    }
+
+    #[test]
+    fn liquidation_bonus_ramps_with_time() {
+        let mut bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        bank.liquity_bonus_ramp = 1_000;
+        assert_eq!(bank.liquidation_bonus(9_900, 0), 550);
+        assert_eq!(bank.liquidation_bonus(9_900, 500), 775);
+        assert_eq!(bank.liquidation_bonus(9_900, 5_000), 1_000);
+    }
+
+    #[test]
+    fn fixed_loan_accrues_simple_interest() {
+        let mut loan = FixedLoan { principal: 1_000_000, rate: 1_000, last_accrued: 0, maturity: 100, ..Default::default() };
+        loan.accrue(SECONDS_PER_YEAR as i64).unwrap();
+        assert_eq!(loan.interest, 100_000);
+        // A second's interest is a fraction of a unit. It's carried, and owed rounded up.
+        loan.accrue(SECONDS_PER_YEAR as i64 + 1).unwrap();
+        assert_eq!(loan.interest, 100_000);
+        assert_eq!(loan.owed(), 1_100_001);
+        // Past maturity the rate keeps running, and going back in time accrues nothing.
+        loan.accrue(SECONDS_PER_YEAR as i64 - 10).unwrap();
+        assert_eq!(loan.owed(), 1_100_001);
+        assert_eq!(loan.last_accrued, SECONDS_PER_YEAR as i64 + 1);
+        assert!(loan.is_overdue(loan.last_accrued, 0));
+    }
+
+    #[test]
+    fn fixed_loan_interest_does_not_depend_on_accrual_frequency() {
+        let new_loan = || FixedLoan { principal: 1_000_000, rate: 1_000, ..Default::default() };
+        let mut accrued_once = new_loan();
+        accrued_once.accrue(1_000).unwrap();
+        let mut accrued_every_second = new_loan();
+        for now in 1..=1_000 {
+            accrued_every_second.accrue(now).unwrap();
+        }
+        assert_eq!(accrued_every_second.interest, accrued_once.interest);
+        assert_eq!(accrued_every_second.owed(), accrued_once.owed());
+    }
+
+    #[test]
+    fn fixed_loan_charges_a_carried_fraction_once_interest_is_paid() {
+        let mut loan = FixedLoan { principal: 1_000, interest: 50, interest_remainder: 1, ..Default::default() };
+        assert_eq!(loan.owed(), 1_051);
+        assert_eq!(loan.repay(50).unwrap(), (50, 0));
+        assert_eq!(loan.owed(), 1_001);
+        assert_eq!(loan.repay(1).unwrap(), (1, 0));
+        assert_eq!((loan.interest_remainder, loan.owed()), (0, 1_000));
+    }
+
+    #[test]
+    fn fixed_loan_repays_interest_before_principal() {
+        let mut loan = FixedLoan { principal: 1_000, interest: 50, rate: 500, last_accrued: 10, maturity: 20, interest_remainder: 0 };
+        assert_eq!(loan.repay(80).unwrap(), (50, 30));
+        assert_eq!((loan.interest, loan.principal), (0, 970));
+        assert!(loan.repay(971).is_err());
+
+        // Repaying in full clears the loan's terms too.
+        assert_eq!(loan.repay(970).unwrap(), (0, 970));
+        assert!(!loan.is_active());
+        assert_eq!((loan.rate, loan.maturity), (0, 0));
+    }
+
+    #[test]
+    fn debt_must_be_zero_or_at_least_the_minimum() {
+        let mut bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        bank.min_debt = 100;
+        assert!(bank.check_min_debt(0).is_ok());
+        assert!(bank.check_min_debt(99).is_err());
+        assert!(bank.check_min_debt(100).is_ok());
+        assert!(bank.check_min_debt_amount(1).is_err());
+    }
+
+    #[test]
+    fn dust_left_behind_is_swept_with_the_rest() {
+        let mut bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        // After a loss a deposit share is worth a thousandth of a unit.
+        bank.total_deposit = 1_000;
+        bank.total_deposit_share = 1_000_000_000;
+        assert_eq!(bank.sweep_deposit_dust(10_000, 9_500).unwrap(), 10_000);
+        assert_eq!(bank.sweep_deposit_dust(20_000, 9_500).unwrap(), 9_500);
+
+        // Two borrow shares per unit of debt, so a single share rounds down to nothing.
+        bank.total_borrow = 1_000;
+        bank.total_borrow_share = 2_000;
+        assert_eq!(bank.sweep_borrow_dust(10, 9).unwrap(), 10);
+        assert_eq!(bank.sweep_borrow_dust(10, 8).unwrap(), 8);
+    }
+
+    #[test]
+    fn origination_fee_rounds_up() {
+        let mut bank = bank(Pubkey::new_unique(), 8_000, 7_500);
+        assert_eq!(bank.origination_fee(1_000_000).unwrap(), 0);
+        bank.borrow_fee = 30;
+        assert_eq!(bank.origination_fee(1_000_000).unwrap(), 3_000);
+        // A fraction of a unit is still charged, so splitting a borrow can't dodge the fee.
+        assert_eq!(bank.origination_fee(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn inflating_an_empty_bank_does_not_take_later_deposits() {
+        let mut bank: Bank = zeroed();
+        let deposit = |bank: &mut Bank, amount: u64| {
+            let shares = bank.shares_for_deposit(amount).unwrap();
+            bank.total_deposit += amount;
+            bank.total_deposit_share += shares;
+            shares
+        };
+
+        // The first depositor takes a single share, then inflates what it's worth by a whole
+        // token's worth of interest or donation.
+        let attacker_shares = deposit(&mut bank, 1);
+        assert_eq!(attacker_shares, 1);
+        let inflation = 1_000_000_000;
+        bank.total_deposit += inflation;
+
+        // Without the virtual deposit this would mint a single share worth half the deposit.
+        let victim_deposit = 2_000_000_000;
+        let victim_shares = deposit(&mut bank, victim_deposit);
+        let victim_value = bank.deposit_shares_to_amount(victim_shares).unwrap();
+        assert!(victim_value >= victim_deposit - victim_deposit / 1_000, "victim redeems {victim_value}");
+        // The inflation mostly accrues to the virtual position, so the attacker loses it.
+        let attacker_value = bank.deposit_shares_to_amount(attacker_shares).unwrap();
+        assert!(attacker_value < inflation / 1_000, "attacker redeems {attacker_value}");
+    }
+
+    #[test]
+    fn delegate_permissions_are_opt_in() {
+        let (owner, delegate, stranger) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut user = user(Pubkey::new_unique(), Pubkey::new_unique());
+        user.owner = owner;
+        // Unset, the default key must not pass for a delegate.
+        assert!(!user.is_owner_or_delegate(&Pubkey::default()));
+
+        user.delegate = delegate;
+        assert!(user.is_owner_or_delegate(&delegate));
+        assert!(!user.can_borrow(&delegate) && !user.can_withdraw(&delegate));
+
+        user.delegate_can_borrow = true;
+        assert!(user.can_borrow(&delegate) && !user.can_withdraw(&delegate));
+        user.delegate_can_withdraw = true;
+        assert!(user.can_withdraw(&delegate));
+
+        // The flags only ever extend to the delegate, and never restrict the owner.
+        assert!(!user.is_owner_or_delegate(&stranger) && !user.can_borrow(&stranger) && !user.can_withdraw(&stranger));
+        user.delegate_can_borrow = false;
+        user.delegate_can_withdraw = false;
+        assert!(user.can_borrow(&owner) && user.can_withdraw(&owner));
+    }
 }
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{hash::hash, instruction::Instruction, program::invoke};
+
+/// Calls `swap(amount_in: u64, min_amount_out: u64)` on an adapter program, Anchor-encoded.
+/// `accounts` are forwarded as-is, so the caller's signature is the only authority the
+/// adapter receives. Callers must measure proceeds from balances rather than trust it.
+pub fn swap<'info>(swap_program: &AccountInfo<'info>, accounts: &[AccountInfo<'info>], amount_in: u64, min_amount_out: u64) -> Result<()> {
+    let mut data = hash(b"global:swap").to_bytes()[..8].to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+    let metas = accounts
+        .iter()
+        .map(|account| AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        })
+        .collect();
+
+    let mut infos = accounts.to_vec();
+    infos.push(swap_program.clone());
+
+    invoke(&Instruction { program_id: swap_program.key(), accounts: metas, data }, &infos)?;
+    Ok(())
+}
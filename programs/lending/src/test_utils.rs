@@ -0,0 +1,38 @@
+use anchor_lang::{AccountDeserialize, Space};
+use pyth_solana_receiver_sdk::price_update::Price;
+
+use crate::constants::{DEFAULT_CLOSE_FACTOR, DEFAULT_LIQUIDATION_BONUS, DEFAULT_MAX_LIQUIDATION_BONUS};
+use crate::{Bank, User};
+
+/// An all-zero account, as `init` leaves it before the handler runs.
+pub fn zeroed<T: AccountDeserialize + Space>() -> T {
+    let data = vec![0u8; 8 + T::INIT_SPACE];
+    T::try_deserialize_unchecked(&mut &data[..]).unwrap()
+}
+
+pub fn price(price: i64, exponent: i32) -> Price {
+    Price { price, conf: 0, exponent, publish_time: 0 }
+}
+
+/// A bank with default risk parameters whose shares trade 1:1 with tokens.
+pub fn bank(mint: anchor_lang::prelude::Pubkey, liquidation_threshold: u64, max_ltv: u64) -> Bank {
+    let mut bank: Bank = zeroed();
+    bank.mint_address = mint;
+    bank.liquity_threshold = liquidation_threshold;
+    bank.max_ltv = max_ltv;
+    bank.liquity_bonus = DEFAULT_LIQUIDATION_BONUS;
+    bank.liquity_max_bonus = DEFAULT_MAX_LIQUIDATION_BONUS;
+    bank.liquity_close_factor = DEFAULT_CLOSE_FACTOR;
+    bank.total_deposit = 1_000_000_000_000_000;
+    bank.total_deposit_share = 1_000_000_000_000_000;
+    bank.total_borrow = 1_000_000_000_000_000;
+    bank.total_borrow_share = 1_000_000_000_000_000;
+    bank
+}
+
+pub fn user(usdc: anchor_lang::prelude::Pubkey, sol: anchor_lang::prelude::Pubkey) -> User {
+    let mut user: User = zeroed();
+    user.usdc_address = usdc;
+    user.sol_address = sol;
+    user
+}
@@ -0,0 +1,203 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+declare_id!("Do57fHSR4PTJAPsZoYC94Xgtz3a6gytFD7aL7s8Y5zPe");
+
+/// Denominator for `Pool::fee_bps`.
+pub const BPS: u64 = 10_000;
+
+/// Minimal constant-product pool implementing the lending program's swap adapter
+/// interface, `swap(amount_in, min_amount_out)`. Meant for tests only: there are no
+/// LP tokens and liquidity can't be withdrawn.
+#[program]
+pub mod mock_amm {
+    use super::*;
+
+    pub fn initialize_pool(ctx: Context<InitializePool>, fee_bps: u64) -> Result<()> {
+        require!(fee_bps < BPS, AmmError::InvalidFee);
+        let pool = &mut ctx.accounts.pool;
+        pool.mint_a = ctx.accounts.mint_a.key();
+        pool.mint_b = ctx.accounts.mint_b.key();
+        pool.fee_bps = fee_bps;
+        pool.bump = ctx.bumps.pool;
+        Ok(())
+    }
+
+    pub fn add_liquidity(ctx: Context<AddLiquidity>, amount_a: u64, amount_b: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        for (from, to, mint, amount) in [
+            (&accounts.provider_a, &accounts.vault_a, &accounts.mint_a, amount_a),
+            (&accounts.provider_b, &accounts.vault_b, &accounts.mint_b, amount_b),
+        ] {
+            let cpi_accounts = TransferChecked {
+                from: from.to_account_info(),
+                to: to.to_account_info(),
+                mint: mint.to_account_info(),
+                authority: accounts.provider.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(accounts.token_program.to_account_info(), cpi_accounts);
+            transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        }
+        Ok(())
+    }
+
+    /// Sells `amount_in` of `mint_in` for as much `mint_out` as `x * y = k` allows after the fee.
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let amount_out = quote(amount_in, accounts.vault_in.amount, accounts.vault_out.amount, accounts.pool.fee_bps)?;
+        require!(amount_out >= min_amount_out, AmmError::SlippageExceeded);
+
+        let transfer_in = TransferChecked {
+            from: accounts.user_source.to_account_info(),
+            to: accounts.vault_in.to_account_info(),
+            mint: accounts.mint_in.to_account_info(),
+            authority: accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(accounts.token_program.to_account_info(), transfer_in);
+        transfer_checked(cpi_ctx, amount_in, accounts.mint_in.decimals)?;
+
+        let pool = &accounts.pool;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"pool",
+            pool.mint_a.as_ref(),
+            pool.mint_b.as_ref(),
+            &[pool.bump],
+        ]];
+        let transfer_out = TransferChecked {
+            from: accounts.vault_out.to_account_info(),
+            to: accounts.user_destination.to_account_info(),
+            mint: accounts.mint_out.to_account_info(),
+            authority: accounts.pool.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(accounts.token_program.to_account_info(), transfer_out).with_signer(signer_seeds);
+        transfer_checked(cpi_ctx, amount_out, accounts.mint_out.decimals)
+    }
+}
+
+/// Output of selling `amount_in` into a pool holding `reserve_in` and `reserve_out`.
+pub fn quote(amount_in: u64, reserve_in: u64, reserve_out: u64, fee_bps: u64) -> Result<u64> {
+    let amount_in = amount_in as u128 * (BPS - fee_bps) as u128 / BPS as u128;
+    let amount_out = (reserve_out as u128)
+        .checked_mul(amount_in)
+        .and_then(|v| v.checked_div(reserve_in as u128 + amount_in))
+        .ok_or(AmmError::EmptyPool)?;
+    Ok(amount_out as u64)
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub fee_bps: u64,
+    pub bump: u8,
+}
+
+impl Pool {
+    pub fn has_pair(&self, mint_in: &Pubkey, mint_out: &Pubkey) -> bool {
+        (*mint_in == self.mint_a && *mint_out == self.mint_b) || (*mint_in == self.mint_b && *mint_out == self.mint_a)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer=payer,
+        space=8 + Pool::INIT_SPACE,
+        seeds=[b"pool", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer=payer,
+        token::mint=mint_a,
+        token::authority=pool,
+        seeds=[b"vault", pool.key().as_ref(), mint_a.key().as_ref()],
+        bump
+    )]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer=payer,
+        token::mint=mint_b,
+        token::authority=pool,
+        seeds=[b"vault", pool.key().as_ref(), mint_b.key().as_ref()],
+        bump
+    )]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    pub provider: Signer<'info>,
+
+    #[account(has_one = mint_a, has_one = mint_b)]
+    pub pool: Account<'info, Pool>,
+
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds=[b"vault", pool.key().as_ref(), mint_a.key().as_ref()], bump)]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds=[b"vault", pool.key().as_ref(), mint_b.key().as_ref()], bump)]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint=mint_a, token::authority=provider)]
+    pub provider_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint=mint_b, token::authority=provider)]
+    pub provider_b: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub user: Signer<'info>,
+
+    #[account(constraint = pool.has_pair(&mint_in.key(), &mint_out.key()) @ AmmError::InvalidPair)]
+    pub pool: Account<'info, Pool>,
+
+    pub mint_in: InterfaceAccount<'info, Mint>,
+    pub mint_out: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds=[b"vault", pool.key().as_ref(), mint_in.key().as_ref()], bump)]
+    pub vault_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds=[b"vault", pool.key().as_ref(), mint_out.key().as_ref()], bump)]
+    pub vault_out: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint=mint_in, token::authority=user)]
+    pub user_source: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint=mint_out)]
+    pub user_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[error_code]
+pub enum AmmError {
+    #[msg("Fee must be below 100%")]
+    InvalidFee,
+    #[msg("Mints do not match the pool")]
+    InvalidPair,
+    #[msg("Pool has no liquidity")]
+    EmptyPool,
+    #[msg("Output below the minimum requested")]
+    SlippageExceeded,
+}
@@ -0,0 +1,77 @@
+import {describe, it} from "node:test";
+import assert from "node:assert";
+import { PublicKey } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { BN } from "@coral-xyz/anchor";
+
+import { expectError, LendingError, LendingFixture, SETTLE_ALL, sol, usdc } from "../bankrun-utils/lendingFixture";
+
+describe("borrow tests", async () => {
+    const fixture = await LendingFixture.create();
+    const { program, signer, mintUSDC, mintSOL } = fixture;
+
+    // Funded before anything is borrowed, funding moves the clock and would accrue interest.
+    const referrer = await fixture.fundedKeypair(new BN(0), new BN(0));
+
+    const borrower = await fixture.initUser(0);
+    await fixture.deposit(borrower, mintSOL, sol(10));
+
+    const repay = async (amount: BN) => program.methods
+        .repay(amount)
+        .accounts({ signer: signer.publicKey, mint: mintUSDC, userAccount: borrower, tokenProgram: TOKEN_PROGRAM_ID })
+        .rpc();
+
+    it("Test Borrow and Repay respect the minimum position size", async () => {
+        await fixture.updateBank(mintUSDC, { minBorrow: usdc(10), minDebt: usdc(5) });
+
+        await expectError(fixture.borrow(borrower, mintUSDC, usdc(1)), LendingError.BelowMinimumBorrow);
+        await fixture.borrow(borrower, mintUSDC, usdc(20));
+
+        // Repaying may close the debt or keep it above `min_debt`, but not leave dust.
+        await expectError(repay(usdc(16)), LendingError.DebtBelowMinimum);
+        await repay(usdc(10));
+        await repay(SETTLE_ALL);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.borrowUsdcShare.toString(), "0");
+        assert.equal(user.borrowUsdc.toString(), "0");
+    })
+
+    it("Test Borrow pays the referrer its share of the origination fee", async () => {
+        // 1% fee, half of it to the referrer.
+        await fixture.updateBank(mintUSDC, { borrowFee: new BN(100), referrerFeeShare: new BN(5_000) });
+        const borrowWithReferrer = async (amount: BN, referrerTokenAccount: PublicKey) => program.methods
+            .borrow(amount)
+            .accounts({
+                signer: signer.publicKey,
+                mint: mintUSDC,
+                collateralMint: mintSOL,
+                userAccount: borrower,
+                owner: signer.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                priceUpdate: fixture.solPriceUpdate,
+                borrowedPriceUpdate: fixture.usdcPriceUpdate,
+                referrerTokenAccount,
+            })
+            .preInstructions([await fixture.refreshObligation(borrower)])
+            .rpc();
+
+        // Borrowers can't refer themselves to get part of their fee back.
+        await expectError(borrowWithReferrer(usdc(100), fixture.ata(mintUSDC)), LendingError.Unauthorized);
+
+        const referrerAccount = fixture.ata(mintUSDC, referrer.publicKey);
+        const ownerBefore = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        const feesBefore = (await program.account.bank.fetch(fixture.bank(mintUSDC))).protocolFees;
+
+        await borrowWithReferrer(usdc(100), referrerAccount);
+
+        const ownerAfter = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        assert.equal(ownerAfter.sub(ownerBefore).toString(), usdc(100).toString());
+        assert.equal((await fixture.tokenBalance(referrerAccount)).toString(), usdc(0.5).toString());
+        const feesAfter = (await program.account.bank.fetch(fixture.bank(mintUSDC))).protocolFees;
+        assert.equal(feesAfter.sub(feesBefore).toString(), usdc(0.5).toString());
+        // The whole fee is added to the debt.
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.borrowUsdc.toString(), usdc(101).toString());
+    })
+});
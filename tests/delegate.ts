@@ -0,0 +1,109 @@
+import {describe, it} from "node:test";
+import assert from "node:assert";
+import { PublicKey } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { BN } from "@coral-xyz/anchor";
+
+import { expectError, LendingError, LendingFixture, sol, usdc } from "../bankrun-utils/lendingFixture";
+
+describe("delegate tests", async () => {
+    const fixture = await LendingFixture.create();
+    const { program, signer, mintUSDC, mintSOL } = fixture;
+
+    const bot = await fixture.fundedKeypair(usdc(1_000), new BN(0));
+
+    const userAccount = await fixture.initUser(0);
+    await fixture.deposit(userAccount, mintSOL, sol(10));
+    await fixture.borrow(userAccount, mintUSDC, usdc(100));
+
+    const setDelegate = (delegate: PublicKey, canBorrow: boolean, canWithdraw: boolean) => program.methods
+        .setDelegate(delegate, canBorrow, canWithdraw)
+        .accounts({ owner: signer.publicKey, userAccount })
+        .rpc();
+
+    const repay = async (amount: BN) => program.methods
+        .repay(amount)
+        .accounts({ signer: bot.publicKey, mint: mintUSDC, userAccount, tokenProgram: TOKEN_PROGRAM_ID })
+        .signers([bot])
+        .rpc();
+
+    const borrow = async (amount: BN) => program.methods
+        .borrow(amount)
+        .accounts({
+            signer: bot.publicKey,
+            mint: mintUSDC,
+            collateralMint: mintSOL,
+            userAccount,
+            owner: signer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            priceUpdate: fixture.solPriceUpdate,
+            borrowedPriceUpdate: fixture.usdcPriceUpdate,
+            referrerTokenAccount: null,
+        })
+        .preInstructions([await fixture.refreshObligation(userAccount)])
+        .signers([bot])
+        .rpc();
+
+    const withdraw = async (amount: BN) => program.methods
+        .withdraw(amount)
+        .accounts({
+            signer: bot.publicKey,
+            mint: mintSOL,
+            otherMint: mintUSDC,
+            userAccount,
+            owner: signer.publicKey,
+            priceUpdate: fixture.solPriceUpdate,
+            otherPriceUpdate: fixture.usdcPriceUpdate,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .preInstructions([await fixture.refreshObligation(userAccount)])
+        .signers([bot])
+        .rpc();
+
+    it("Test only the owner can act before a delegate is set", async () => {
+        await expectError(repay(usdc(11)), LendingError.Unauthorized);
+        await expectError(borrow(usdc(11)), LendingError.Unauthorized);
+    })
+
+    it("Test Delegate repays from its own tokens", async () => {
+        await setDelegate(bot.publicKey, false, false);
+        const botBefore = await fixture.tokenBalance(fixture.ata(mintUSDC, bot.publicKey));
+
+        await repay(usdc(50));
+
+        const botAfter = await fixture.tokenBalance(fixture.ata(mintUSDC, bot.publicKey));
+        assert.equal(botBefore.sub(botAfter).toString(), usdc(50).toString());
+        const user = await program.account.user.fetch(userAccount);
+        assert.equal(user.borrowUsdc.toString(), usdc(50).toString());
+        // Borrowing and withdrawing need their own permission.
+        await expectError(borrow(usdc(12)), LendingError.Unauthorized);
+        await expectError(withdraw(sol(2)), LendingError.Unauthorized);
+    })
+
+    it("Test Delegate borrows and withdraws to the owner", async () => {
+        await setDelegate(bot.publicKey, true, true);
+        const ownerUsdcBefore = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        const ownerSolBefore = await fixture.tokenBalance(fixture.ata(mintSOL));
+        const botUsdcBefore = await fixture.tokenBalance(fixture.ata(mintUSDC, bot.publicKey));
+
+        await borrow(usdc(10));
+        await withdraw(sol(1));
+
+        const ownerUsdcAfter = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        const ownerSolAfter = await fixture.tokenBalance(fixture.ata(mintSOL));
+        assert.equal(ownerUsdcAfter.sub(ownerUsdcBefore).toString(), usdc(10).toString());
+        assert.equal(ownerSolAfter.sub(ownerSolBefore).toString(), sol(1).toString());
+        const botUsdcAfter = await fixture.tokenBalance(fixture.ata(mintUSDC, bot.publicKey));
+        assert.equal(botUsdcAfter.toString(), botUsdcBefore.toString());
+    })
+
+    it("Test Revoking the delegate", async () => {
+        await setDelegate(PublicKey.default, true, true);
+
+        const user = await program.account.user.fetch(userAccount);
+        assert.equal(user.delegate.toBase58(), PublicKey.default.toBase58());
+        assert.equal(user.delegateCanBorrow, false);
+        assert.equal(user.delegateCanWithdraw, false);
+        await expectError(repay(usdc(5)), LendingError.Unauthorized);
+    })
+});
@@ -1,4 +1,5 @@
 import {describe, it} from "node:test";
+import assert from "node:assert";
 import { BanksClient, ProgramTestContext, startAnchor } from 'solana-bankrun'
 import { clusterApiUrl, Connection, PublicKey } from "@solana/web3.js";
 import { BankrunProvider } from 'anchor-bankrun'
@@ -24,6 +25,7 @@ describe("lending program tests",  async () => {
     let banksClient: BanksClient;
     let usdcBankAccount: PublicKey;
     let solBankAccount: PublicKey;
+    let userAccount: PublicKey;
 
     const pyth = new PublicKey('7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE');
     const accountInfo = await devnetConnection.getAccountInfo(pyth);
@@ -54,6 +56,12 @@ describe("lending program tests",  async () => {
 
     context.setAccount(solUsdPriceFeedAccountPubKey, feedAccountInfo);
 
+    const SOL_USD_FEED_ID = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+    const borrowedPriceFeedAccount = pythSolanaReceiver.getPriceFeedAccountAddress(0, SOL_USD_FEED_ID);
+    const borrowedFeedAccountInfo = await devnetConnection.getAccountInfo(borrowedPriceFeedAccount);
+
+    context.setAccount(borrowedPriceFeedAccount, borrowedFeedAccountInfo);
+
     console.log(`Price Feed: ${solUsdPriceFeedAccount}`);
     console.log(`Pyth Account Info: ${JSON.stringify(accountInfo)}`);
 
@@ -90,12 +98,19 @@ describe("lending program tests",  async () => {
         program.programId
     );
 
+    [userAccount] = PublicKey.findProgramAddressSync(
+        [Buffer.from('user'), signer.publicKey.toBuffer(), Buffer.from([0])],
+        program.programId
+    );
+
     console.log("USDC Bank Account: ", usdcBankAccount.toBase58());
     console.log("SOL Bank Account: ", solBankAccount.toBase58());
     
+    const feedIdBytes = (feedId: string) => Array.from(Buffer.from(feedId.slice(2), "hex"));
+
     it("Test init User", async() =>{
         const initUserTx = await program.methods
-            .initializeUser(mintUSDC)
+            .initializeUser(0, mintUSDC)
             .accounts({
                 signer: signer.publicKey
             })
@@ -105,10 +120,11 @@ describe("lending program tests",  async () => {
 
     it("test init and Fund USDC Bank ",  async() =>{
         const initUSDCBankTx = await program.methods
-        .initializeBank(new BN(1), new BN(1))
+        .initializeBank(new BN(8000), new BN(7500), feedIdBytes(SOL_FEED_ID))
         .accounts({
             mint: mintUSDC,
             signer: signer.publicKey,
+            programData: null,
             tokenProgram: TOKEN_PROGRAM_ID
         })
         .rpc({commitment:"confirmed"})
@@ -130,10 +146,11 @@ describe("lending program tests",  async () => {
 
     it("test init and Fund SOL Bank ",  async() =>{
         const initSOLBankTx = await program.methods
-        .initializeBank(new BN(1), new BN(1))
+        .initializeBank(new BN(8000), new BN(7500), feedIdBytes(SOL_USD_FEED_ID))
         .accounts({
             signer: signer.publicKey,
             mint: mintSOL,
+            programData: null,
             tokenProgram: TOKEN_PROGRAM_ID
         })
         .rpc({commitment:"confirmed"})
@@ -182,21 +199,39 @@ describe("lending program tests",  async () => {
         .accounts({
             signer: signer.publicKey,
             mint: mintUSDC,
+            userAccount,
             tokenProgram: TOKEN_PROGRAM_ID
         })
         .rpc({commitment: "confirmed"});
         console.log("Deposite USDC: ", depositeUSDC);
     })
 
+    const refreshObligation = () => program.methods
+        .refreshObligation()
+        .accounts({
+            mint: mintSOL,
+            priceUpdate: borrowedPriceFeedAccount,
+            otherMint: mintUSDC,
+            otherPriceUpdate: solUsdPriceFeedAccount,
+            userAccount,
+        })
+        .instruction();
+
     it("Test Borrow", async()=>{
         const borrowSOl = await program.methods
         .borrow(new BN(1))
         .accounts({
             signer: signer.publicKey,
             mint: mintSOL,
+            collateralMint: mintUSDC,
+            userAccount,
+            owner: signer.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
-            priceUpdate: solUsdPriceFeedAccount
+            priceUpdate: solUsdPriceFeedAccount,
+            borrowedPriceUpdate: borrowedPriceFeedAccount,
+            referrerTokenAccount: null,
         })
+        .preInstructions([await refreshObligation()])
         .rpc({commitment: "confirmed"});
         console.log("Borrow Sol: ", borrowSOl);
     })
@@ -207,6 +242,7 @@ describe("lending program tests",  async () => {
         .accounts({
             signer: signer.publicKey,
             mint: mintSOL,
+            userAccount,
             tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc({commitment: "confirmed"});
@@ -219,9 +255,77 @@ describe("lending program tests",  async () => {
         .accounts({
             signer: signer.publicKey,
             mint: mintUSDC,
+            otherMint: mintSOL,
+            userAccount,
+            owner: signer.publicKey,
+            priceUpdate: solUsdPriceFeedAccount,
+            otherPriceUpdate: borrowedPriceFeedAccount,
             tokenProgram: TOKEN_PROGRAM_ID,
         })
+        .preInstructions([await refreshObligation()])
         .rpc({commitment: "confirmed"});
         console.log("WithDraw USDC: ", withdrawUSDC);
     })
+
+    const endBatch = () => program.methods
+        .endBatch()
+        .accounts({
+            userAccount,
+            mint: mintSOL,
+            priceUpdate: borrowedPriceFeedAccount,
+            otherMint: mintUSDC,
+            otherPriceUpdate: solUsdPriceFeedAccount,
+        })
+        .instruction();
+
+    const borrowSOLInstruction = (amount: BN) => program.methods
+        .borrow(amount)
+        .accounts({
+            signer: signer.publicKey,
+            mint: mintSOL,
+            collateralMint: mintUSDC,
+            userAccount,
+            owner: signer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            priceUpdate: solUsdPriceFeedAccount,
+            borrowedPriceUpdate: borrowedPriceFeedAccount,
+            referrerTokenAccount: null,
+        })
+        .instruction();
+
+    it("Test Batch", async () =>{
+        const batchTx = await program.methods
+        .startBatch()
+        .accounts({
+            signer: signer.publicKey,
+            userAccount,
+        })
+        .preInstructions([await refreshObligation()])
+        .postInstructions([await borrowSOLInstruction(new BN(1)), await endBatch()])
+        .rpc({commitment: "confirmed"});
+        console.log("Batch: ", batchTx);
+
+        const user = await program.account.user.fetch(userAccount);
+        assert.equal(user.inBatch, false);
+    })
+
+    it("Test Batch ending unhealthy is rejected", async () =>{
+        // Far past the borrow limit, the borrow itself skips its check inside the batch.
+        const borrowAmount = new BN(5_000).mul(new BN(10).pow(new BN(9)));
+        await assert.rejects(
+            program.methods
+            .startBatch()
+            .accounts({
+                signer: signer.publicKey,
+                userAccount,
+            })
+            .preInstructions([await refreshObligation()])
+            .postInstructions([await borrowSOLInstruction(borrowAmount), await endBatch()])
+            .rpc({commitment: "confirmed"}),
+            /Unhealthy/,
+        );
+
+        const user = await program.account.user.fetch(userAccount);
+        assert.equal(user.inBatch, false);
+    })
 });
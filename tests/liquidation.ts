@@ -0,0 +1,175 @@
+import {describe, it} from "node:test";
+import assert from "node:assert";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { BN } from "@coral-xyz/anchor";
+
+import { expectError, LendingError, LendingFixture, SETTLE_ALL, sol, usdc } from "../bankrun-utils/lendingFixture";
+
+describe("liquidation tests", async () => {
+    const fixture = await LendingFixture.create();
+    const { program, signer, mintUSDC, mintSOL } = fixture;
+
+    // Funded before anything is borrowed, funding moves the clock and would accrue interest.
+    const liquidator = await fixture.fundedKeypair(usdc(10_000), new BN(0));
+
+    // 10 SOL backing 700 USDC: health 1.14 at $100.
+    const borrower = await fixture.initUser(0);
+    await fixture.deposit(borrower, mintSOL, sol(10));
+    await fixture.borrow(borrower, mintUSDC, usdc(700));
+
+    const liquidate = async (repayAmount: BN) => program.methods
+        .liquidate(repayAmount)
+        .accounts({
+            liquidator: liquidator.publicKey,
+            collateralPriceUpdate: fixture.solPriceUpdate,
+            borrowedPriceUpdate: fixture.usdcPriceUpdate,
+            collateralMint: mintSOL,
+            borrowedMint: mintUSDC,
+            userAccount: borrower,
+            liquidatorAccount: null,
+            liquidatorCollateralTokenAccount: fixture.ata(mintSOL, liquidator.publicKey),
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .preInstructions([await fixture.refreshObligation(borrower)])
+        .signers([liquidator])
+        .rpc();
+
+    it("Test Liquidate healthy obligation is rejected", async () => {
+        await expectError(liquidate(usdc(100)), LendingError.NotUndercollateralized);
+    })
+
+    it("Test Liquidate at health 0.7", async () => {
+        // $612.50 of SOL weighted at 80% against $700 of debt.
+        await fixture.setSolPrice(61.25);
+        await fixture.setUsdcPrice(1);
+        const solBefore = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+        const usdcBefore = await fixture.tokenBalance(fixture.ata(mintUSDC, liquidator.publicKey));
+
+        const liquidateTx = await liquidate(usdc(350));
+        console.log("Liquidate: ", liquidateTx);
+
+        // Past break-even the max 10% bonus applies: 6.2857 SOL seized, of which the
+        // protocol keeps a tenth of the 0.5714 SOL bonus.
+        const solAfter = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+        const usdcAfter = await fixture.tokenBalance(fixture.ata(mintUSDC, liquidator.publicKey));
+        assert.equal(usdcBefore.sub(usdcAfter).toString(), usdc(350).toString());
+        assert.equal(solAfter.sub(solBefore).toString(), "6228571428");
+
+        const solBank = await program.account.bank.fetch(fixture.bank(mintSOL));
+        assert.equal(solBank.protocolFees.toString(), "57142857");
+        const user = await program.account.user.fetch(borrower);
+        assert.ok(user.liquidatableSince.gtn(0));
+    })
+
+    it("Test Liquidate into the liquidator's obligation", async () => {
+        // Credited as deposit shares, so no collateral token account is needed.
+        await program.methods
+            .initializeUser(0, mintUSDC)
+            .accounts({ signer: liquidator.publicKey })
+            .signers([liquidator])
+            .rpc();
+        const liquidatorAccount = fixture.userAccount(0, liquidator.publicKey);
+        const solBefore = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+
+        await program.methods
+            .liquidate(usdc(50))
+            .accounts({
+                liquidator: liquidator.publicKey,
+                collateralPriceUpdate: fixture.solPriceUpdate,
+                borrowedPriceUpdate: fixture.usdcPriceUpdate,
+                collateralMint: mintSOL,
+                borrowedMint: mintUSDC,
+                userAccount: borrower,
+                liquidatorAccount,
+                liquidatorCollateralTokenAccount: null,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .preInstructions([await fixture.refreshObligation(borrower)])
+            .signers([liquidator])
+            .rpc();
+
+        const credited = await program.account.user.fetch(liquidatorAccount);
+        assert.ok(credited.depositSolShare.gtn(0));
+        assert.equal(credited.solAddress.toBase58(), mintSOL.toBase58());
+        const solAfter = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+        assert.equal(solAfter.toString(), solBefore.toString());
+    })
+
+    it("Test Repay For", async () => {
+        const before = await program.account.user.fetch(borrower);
+        const repayForTx = await program.methods
+            .repayFor(usdc(100))
+            .accounts({
+                payer: liquidator.publicKey,
+                mint: mintUSDC,
+                userAccount: borrower,
+                payerTokenAccount: fixture.ata(mintUSDC, liquidator.publicKey),
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .signers([liquidator])
+            .rpc();
+        console.log("Repay For: ", repayForTx);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.ok(user.borrowUsdcShare.lt(before.borrowUsdcShare));
+        assert.equal(user.owner.toBase58(), signer.publicKey.toBase58());
+    })
+
+    it("Test Preview Liquidation matches Liquidate", async () => {
+        const preview = await program.methods
+            .previewLiquidation()
+            .accounts({
+                collateralPriceUpdate: fixture.solPriceUpdate,
+                borrowedPriceUpdate: fixture.usdcPriceUpdate,
+                collateralMint: mintSOL,
+                borrowedMint: mintUSDC,
+                userAccount: borrower,
+            })
+            .view();
+        assert.ok(preview.liquidatable);
+        assert.ok(preview.collateralForMax.gtn(0));
+
+        const solBefore = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+        const usdcBefore = await fixture.tokenBalance(fixture.ata(mintUSDC, liquidator.publicKey));
+        const feesBefore = (await program.account.bank.fetch(fixture.bank(mintSOL))).protocolFees;
+
+        // Repaying the quoted `max_repay` is accepted and repays `repay_for_max`.
+        await liquidate(preview.maxRepay);
+
+        const solAfter = await fixture.tokenBalance(fixture.ata(mintSOL, liquidator.publicKey));
+        const usdcAfter = await fixture.tokenBalance(fixture.ata(mintUSDC, liquidator.publicKey));
+        const feesAfter = (await program.account.bank.fetch(fixture.bank(mintSOL))).protocolFees;
+        assert.equal(usdcBefore.sub(usdcAfter).toString(), preview.repayForMax.toString());
+        // What's seized is split between the liquidator and the protocol's cut of the bonus.
+        const seized = solAfter.sub(solBefore).add(feesAfter.sub(feesBefore));
+        assert.equal(seized.toString(), preview.collateralForMax.toString());
+    })
+
+    it("Test Handle Bad Debt", async () => {
+        const handleBadDebt = () => program.methods
+            .handleBadDebt()
+            .accounts({ authority: signer.publicKey, mint: mintUSDC, userAccount: borrower })
+            .rpc();
+        await expectError(handleBadDebt(), LendingError.NoBadDebt);
+
+        // At $10 the remaining collateral can't cover the debt plus bonus, so all of it is seized.
+        await fixture.setSolPrice(10);
+        await fixture.setUsdcPrice(1);
+        await liquidate(SETTLE_ALL);
+        const liquidated = await program.account.user.fetch(borrower);
+        assert.equal(liquidated.depositSolShare.toString(), "0");
+        assert.ok(liquidated.borrowUsdcShare.gtn(0));
+
+        const bankBefore = await program.account.bank.fetch(fixture.bank(mintUSDC));
+        const badDebtTx = await handleBadDebt();
+        console.log("Handle Bad Debt: ", badDebtTx);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.borrowUsdcShare.toString(), "0");
+        assert.equal(user.liquidatableSince.toString(), "0");
+        const bank = await program.account.bank.fetch(fixture.bank(mintUSDC));
+        assert.ok(bank.totalBorrowShare.lt(bankBefore.totalBorrowShare));
+        // The loss is taken from protocol fees first, then from depositors.
+        assert.ok(bank.totalDeposit.add(bank.protocolFees).lt(bankBefore.totalDeposit.add(bankBefore.protocolFees)));
+    })
+});
@@ -0,0 +1,63 @@
+import {describe, it} from "node:test";
+import assert from "node:assert";
+import { PublicKey } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { BN } from "@coral-xyz/anchor";
+import { createMint, mintTo } from "spl-token-bankrun";
+
+import { LendingFixture } from "../bankrun-utils/lendingFixture";
+
+describe("reward tests", async () => {
+    const fixture = await LendingFixture.create();
+    const { program, signer, mintUSDC } = fixture;
+    const banksClient = fixture.context.banksClient;
+
+    // @ts-ignore
+    const rewardMint = await createMint(banksClient, signer, signer.publicKey, null, 6);
+    const [rewardVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reward_vault"), fixture.bank(mintUSDC).toBuffer(), rewardMint.toBuffer()],
+        program.programId
+    );
+    // The fixture's lender is the USDC bank's only depositor.
+    const lender = fixture.userAccount(1);
+    const EMISSION_PER_SECOND = 1_000;
+
+    it("Test Claim Rewards", async () => {
+        const start = Number((await banksClient.getClock()).unixTimestamp);
+        const addRewardTx = await program.methods
+            .addReward(0, { deposit: {} }, new BN(EMISSION_PER_SECOND), new BN(start), new BN(start + 1_000))
+            .accounts({
+                authority: signer.publicKey,
+                mint: mintUSDC,
+                rewardMint,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        console.log("Add Reward: ", addRewardTx);
+        // @ts-ignore
+        await mintTo(banksClient, signer, rewardMint, rewardVault, signer, 1_000_000_000);
+
+        await fixture.wrapper.moveTimeForward(100);
+        const claimTx = await program.methods
+            .claimRewards()
+            .accounts({
+                signer: signer.publicKey,
+                mint: mintUSDC,
+                rewardMint,
+                userAccount: lender,
+                owner: signer.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        console.log("Claim Rewards: ", claimTx);
+
+        // The lender holds every deposit share, so they get all of the emission since `start` less index rounding.
+        const end = Number((await banksClient.getClock()).unixTimestamp);
+        const claimed = (await fixture.tokenBalance(fixture.ata(rewardMint))).toNumber();
+        assert.ok(claimed >= EMISSION_PER_SECOND * 100 * 0.99, `claimed ${claimed}`);
+        assert.ok(claimed <= EMISSION_PER_SECOND * (end - start), `claimed ${claimed}`);
+
+        const user = await program.account.user.fetch(lender);
+        assert.equal(user.usdcRewards[0].accrued.toString(), "0");
+    })
+});
@@ -0,0 +1,294 @@
+import {describe, it} from "node:test";
+import assert from "node:assert";
+import { AccountMeta, Keypair, PublicKey } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { BN, Program } from "@coral-xyz/anchor";
+import { createAccount, createMint, mintTo } from "spl-token-bankrun";
+
+import MOCK_AMM_IDL from "../target/idl/mock_amm.json";
+import { MockAmm } from "../target/types/mock_amm";
+import { expectError, LendingError, LendingFixture, SETTLE_ALL, sol, usdc, USDC_DECIMALS } from "../bankrun-utils/lendingFixture";
+
+describe("swap adapter tests", async () => {
+    const fixture = await LendingFixture.create();
+    const { program, signer, mintUSDC, mintSOL } = fixture;
+    const banksClient = fixture.context.banksClient;
+    const amm = new Program<MockAmm>(MOCK_AMM_IDL as MockAmm, fixture.wrapper.provider);
+
+    const vault = (pool: PublicKey, mint: PublicKey) => PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), pool.toBuffer(), mint.toBuffer()],
+        amm.programId
+    )[0];
+
+    const createPool = async (mintA: PublicKey, mintB: PublicKey, feeBps: number, amountA: BN, amountB: BN) => {
+        const [pool] = PublicKey.findProgramAddressSync(
+            [Buffer.from("pool"), mintA.toBuffer(), mintB.toBuffer()],
+            amm.programId
+        );
+        await amm.methods
+            .initializePool(new BN(feeBps))
+            .accounts({ payer: signer.publicKey, mintA, mintB, tokenProgram: TOKEN_PROGRAM_ID })
+            .rpc();
+        await amm.methods
+            .addLiquidity(amountA, amountB)
+            .accounts({
+                provider: signer.publicKey,
+                pool,
+                mintA,
+                mintB,
+                providerA: fixture.ata(mintA),
+                providerB: fixture.ata(mintB),
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        return pool;
+    };
+
+    // Priced like the oracles, and one that keeps 90% of every swap as its fee.
+    const fairPool = await createPool(mintUSDC, mintSOL, 0, usdc(1_000_000), sol(10_000));
+    const lossyPool = await createPool(mintSOL, mintUSDC, 9_000, sol(1_000), usdc(100_000));
+
+    // A second stablecoin bank the obligations below don't hold yet, supplied by another obligation.
+    const USDT_FEED_ID = Array(32).fill(3);
+    // @ts-ignore
+    const mintUSDT = await createMint(banksClient, signer, signer.publicKey, null, USDC_DECIMALS);
+    const usdtPriceUpdate = Keypair.generate().publicKey;
+    await program.methods
+        .initializeBank(new BN(8000), new BN(7500), USDT_FEED_ID)
+        .accounts({ signer: signer.publicKey, mint: mintUSDT, tokenProgram: TOKEN_PROGRAM_ID, programData: null })
+        .rpc();
+    await fixture.setPrice(usdtPriceUpdate, USDT_FEED_ID, 1);
+    // @ts-ignore
+    const usdtAccount = await createAccount(banksClient, signer, mintUSDT, signer.publicKey);
+    // @ts-ignore
+    await mintTo(banksClient, signer, mintUSDT, usdtAccount, signer, 1_000_000_000_000);
+    await fixture.deposit(await fixture.initUser(3), mintUSDT, usdc(100_000));
+    const usdtPool = await createPool(mintUSDT, mintUSDC, 0, usdc(100_000), usdc(100_000));
+    const solUsdtPool = await createPool(mintSOL, mintUSDT, 0, sol(1_000), usdc(100_000));
+
+    // Token accounts the adapter can be pointed at instead of the ones the lending program measures.
+    // @ts-ignore
+    const strayUSDC = await createAccount(banksClient, signer, mintUSDC, signer.publicKey, Keypair.generate());
+    // @ts-ignore
+    const straySOL = await createAccount(banksClient, signer, mintSOL, signer.publicKey, Keypair.generate());
+
+    const borrower = await fixture.initUser(0);
+
+    const swapAccounts = (pool: PublicKey, mintIn: PublicKey, mintOut: PublicKey, destination = fixture.ata(mintOut)): AccountMeta[] => [
+        { pubkey: signer.publicKey, isSigner: true, isWritable: true },
+        { pubkey: pool, isSigner: false, isWritable: false },
+        { pubkey: mintIn, isSigner: false, isWritable: false },
+        { pubkey: mintOut, isSigner: false, isWritable: false },
+        { pubkey: vault(pool, mintIn), isSigner: false, isWritable: true },
+        { pubkey: vault(pool, mintOut), isSigner: false, isWritable: true },
+        { pubkey: fixture.ata(mintIn), isSigner: false, isWritable: true },
+        { pubkey: destination, isSigner: false, isWritable: true },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ];
+
+    /** What the pool pays for `amountIn` right now, mirroring `mock_amm::quote`. */
+    const quote = async (pool: PublicKey, mintIn: PublicKey, mintOut: PublicKey, amountIn: BN, feeBps: number) => {
+        const reserveIn = await fixture.tokenBalance(vault(pool, mintIn));
+        const reserveOut = await fixture.tokenBalance(vault(pool, mintOut));
+        const amountAfterFee = amountIn.muln(10_000 - feeBps).divn(10_000);
+        return reserveOut.mul(amountAfterFee).div(reserveIn.add(amountAfterFee));
+    };
+
+    const leverage = async (depositAmount: BN, borrowAmount: BN, minAmountOut: BN, destination?: PublicKey) => program.methods
+        .leverage(depositAmount, borrowAmount, minAmountOut)
+        .accounts({
+            signer: signer.publicKey,
+            collateralMint: mintSOL,
+            borrowedMint: mintUSDC,
+            userAccount: borrower,
+            collateralPriceUpdate: fixture.solPriceUpdate,
+            borrowedPriceUpdate: fixture.usdcPriceUpdate,
+            swapProgram: amm.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(swapAccounts(fairPool, mintUSDC, mintSOL, destination))
+        .preInstructions([await fixture.refreshObligation(borrower)])
+        .rpc();
+
+    const swapCollateral = async (amount: BN, minAmountOut: BN, pool: PublicKey, destination?: PublicKey) => program.methods
+        .swapCollateral(amount, minAmountOut)
+        .accounts({
+            signer: signer.publicKey,
+            fromMint: mintSOL,
+            toMint: mintUSDC,
+            userAccount: borrower,
+            fromPriceUpdate: fixture.solPriceUpdate,
+            toPriceUpdate: fixture.usdcPriceUpdate,
+            otherMint: null,
+            otherBank: null,
+            otherPriceUpdate: null,
+            swapProgram: amm.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(swapAccounts(pool, mintSOL, mintUSDC, destination))
+        .preInstructions([await fixture.refreshObligation(borrower)])
+        .rpc();
+
+    const refinanceDebt = async (borrowAmount: BN, minAmountOut: BN, pool: PublicKey, destination?: PublicKey) => program.methods
+        .refinanceDebt(borrowAmount, minAmountOut)
+        .accounts({
+            signer: signer.publicKey,
+            sourceMint: mintUSDC,
+            targetMint: mintSOL,
+            userAccount: borrower,
+            sourcePriceUpdate: fixture.usdcPriceUpdate,
+            targetPriceUpdate: fixture.solPriceUpdate,
+            otherMint: null,
+            otherBank: null,
+            otherPriceUpdate: null,
+            swapProgram: amm.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(swapAccounts(pool, mintSOL, mintUSDC, destination))
+        .preInstructions([await fixture.refreshObligation(borrower)])
+        .rpc();
+
+    it("Test Leverage", async () => {
+        const expectedOut = await quote(fairPool, mintUSDC, mintSOL, usdc(500), 0);
+        const leverageTx = await leverage(sol(10), usdc(500), expectedOut);
+        console.log("Leverage: ", leverageTx);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.depositSol.toString(), sol(10).add(expectedOut).toString());
+        assert.equal(user.borrowUsdc.toString(), usdc(500).toString());
+    })
+
+    it("Test Leverage fails when the swap pays less than the minimum", async () => {
+        // The adapter pays out, but not into the account the lending program measures.
+        await expectError(leverage(sol(1), usdc(100), sol(0.9), straySOL), LendingError.SlippageExceeded);
+    })
+
+    it("Test Leverage fails the final health check", async () => {
+        // ~$6,500 of collateral can't back $5,500 of debt at a 75% max LTV.
+        await expectError(leverage(new BN(0), usdc(5_000), new BN(0)), LendingError.OverBorrowableAmount);
+    })
+
+    it("Test Swap Collateral", async () => {
+        const before = await program.account.user.fetch(borrower);
+        const expectedOut = await quote(fairPool, mintSOL, mintUSDC, sol(1), 0);
+        const swapTx = await swapCollateral(sol(1), expectedOut, fairPool);
+        console.log("Swap Collateral: ", swapTx);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.depositSol.toString(), before.depositSol.sub(sol(1)).toString());
+        assert.equal(user.depositUsdc.toString(), expectedOut.toString());
+        assert.equal(user.borrowUsdc.toString(), before.borrowUsdc.toString());
+    })
+
+    it("Test Swap Collateral fails when the swap pays less than the minimum", async () => {
+        await expectError(swapCollateral(sol(1), usdc(90), fairPool, strayUSDC), LendingError.SlippageExceeded);
+    })
+
+    it("Test Swap Collateral fails the final health check", async () => {
+        // Swapping every SOL through a pool that keeps 90% leaves the debt uncovered.
+        await expectError(swapCollateral(SETTLE_ALL, new BN(0), lossyPool), LendingError.Unhealthy);
+    })
+
+    it("Test Swap Collateral into a mint the obligation doesn't hold", async () => {
+        // 10 SOL backing 300 USDC. Swapping all of the SOL frees its leg for USDT.
+        const swapper = await fixture.initUser(4);
+        await fixture.deposit(swapper, mintSOL, sol(10));
+        await fixture.borrow(swapper, mintUSDC, usdc(300));
+
+        const swapIntoUSDT = async (amount: BN) => program.methods
+            .swapCollateral(amount, new BN(0))
+            .accounts({
+                signer: signer.publicKey,
+                fromMint: mintSOL,
+                toMint: mintUSDT,
+                userAccount: swapper,
+                fromPriceUpdate: fixture.solPriceUpdate,
+                toPriceUpdate: usdtPriceUpdate,
+                otherMint: mintUSDC,
+                otherBank: fixture.bank(mintUSDC),
+                otherPriceUpdate: fixture.usdcPriceUpdate,
+                swapProgram: amm.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .remainingAccounts(swapAccounts(solUsdtPool, mintSOL, mintUSDT))
+            .preInstructions([await fixture.refreshObligation(swapper)])
+            .rpc();
+
+        // Swapping part of the SOL leaves it bound, with no room for USDT.
+        await expectError(swapIntoUSDT(sol(5)), LendingError.InvalidMint);
+
+        const vaultBefore = await fixture.tokenBalance(vault(solUsdtPool, mintUSDT));
+        const swapTx = await swapIntoUSDT(SETTLE_ALL);
+        console.log("Swap Collateral into USDT: ", swapTx);
+        const swappedOut = vaultBefore.sub(await fixture.tokenBalance(vault(solUsdtPool, mintUSDT)));
+
+        const user = await program.account.user.fetch(swapper);
+        assert.equal(user.solAddress.toBase58(), mintUSDT.toBase58());
+        assert.equal(user.depositSol.toString(), swappedOut.toString());
+        // The USDC debt never moved.
+        assert.equal(user.usdcAddress.toBase58(), mintUSDC.toBase58());
+        assert.equal(user.borrowUsdc.toString(), usdc(300).toString());
+    })
+
+    it("Test Refinance Debt fails when the swap pays less than the minimum", async () => {
+        await expectError(refinanceDebt(sol(6), usdc(500), fairPool, strayUSDC), LendingError.SlippageExceeded);
+    })
+
+    it("Test Refinance Debt fails the final health check", async () => {
+        // The USDC debt is repaid, but with 60 SOL of new debt against ~$1,500 of collateral.
+        await expectError(refinanceDebt(sol(60), usdc(500), lossyPool), LendingError.OverBorrowableAmount);
+    })
+
+    it("Test Refinance Debt", async () => {
+        const usdcBefore = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        const expectedOut = await quote(fairPool, mintSOL, mintUSDC, sol(6), 0);
+        const refinanceTx = await refinanceDebt(sol(6), usdc(500), fairPool);
+        console.log("Refinance Debt: ", refinanceTx);
+
+        const user = await program.account.user.fetch(borrower);
+        assert.equal(user.borrowUsdcShare.toString(), "0");
+        assert.equal(user.borrowSol.toString(), sol(6).toString());
+        // Proceeds beyond the 500 USDC owed stay with the signer.
+        const usdcAfter = await fixture.tokenBalance(fixture.ata(mintUSDC));
+        assert.equal(usdcAfter.sub(usdcBefore).toString(), expectedOut.sub(usdc(500)).toString());
+    })
+
+    it("Test Refinance Debt into a mint the obligation doesn't hold", async () => {
+        // 10 SOL backing 300 USDC. The USDC leg is freed by the repayment and moves to USDT.
+        const stableBorrower = await fixture.initUser(2);
+        await fixture.deposit(stableBorrower, mintSOL, sol(10));
+        await fixture.borrow(stableBorrower, mintUSDC, usdc(300));
+
+        const refinanceIntoUSDT = async (borrowAmount: BN, minAmountOut: BN) => program.methods
+            .refinanceDebt(borrowAmount, minAmountOut)
+            .accounts({
+                signer: signer.publicKey,
+                sourceMint: mintUSDC,
+                targetMint: mintUSDT,
+                userAccount: stableBorrower,
+                sourcePriceUpdate: fixture.usdcPriceUpdate,
+                targetPriceUpdate: usdtPriceUpdate,
+                otherMint: mintSOL,
+                otherBank: fixture.bank(mintSOL),
+                otherPriceUpdate: fixture.solPriceUpdate,
+                swapProgram: amm.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .remainingAccounts(swapAccounts(usdtPool, mintUSDT, mintUSDC))
+            .preInstructions([await fixture.refreshObligation(stableBorrower)])
+            .rpc();
+
+        // Repaying part of the USDC leaves it bound, with no room for USDT.
+        await expectError(refinanceIntoUSDT(usdc(100), usdc(90)), LendingError.InvalidMint);
+
+        const refinanceTx = await refinanceIntoUSDT(usdc(310), usdc(300));
+        console.log("Refinance Debt into USDT: ", refinanceTx);
+
+        const user = await program.account.user.fetch(stableBorrower);
+        assert.equal(user.usdcAddress.toBase58(), mintUSDT.toBase58());
+        assert.equal(user.borrowUsdc.toString(), usdc(310).toString());
+        // The SOL collateral never moved.
+        assert.equal(user.solAddress.toBase58(), mintSOL.toBase58());
+        assert.equal(user.depositSol.toString(), sol(10).toString());
+    })
+});